@@ -0,0 +1,250 @@
+use crate::conf::SpConf;
+use crate::event::{Evt, Sink};
+use crate::protocol::{key, sm3, sm4_enc_cbc, sm4_enc_ecb, BlockMode, FrmDec, Rsp};
+use crate::{
+  f, mk_err_str, soft, we, Dbg, Opt, Rst, Str, FRM_HEAD_LEN, FRM_MIN_LEN, FRM_TAIL_LEN, IV_LEN,
+  KEY_LEN,
+};
+use bytes::Bytes;
+use core::time::Duration as Durn;
+use serialport::{ClearBuffer, DataBits, Parity, SerialPort, StopBits};
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+pub const BENCH_KEY: [u8; KEY_LEN] = *b"uifs-bench-key!!";
+pub const BENCH_IV: [u8; IV_LEN] = [0x5A; IV_LEN];
+pub const BENCH_WAIT: Durn = Durn::from_millis(500);
+pub const BENCH_CSV_HEADER: &str = "label,unix_s,op,frames,size,window,ok,bad,lost,elapsed_s,\
+  device_mbps,bound_mbps,utilisation";
+
+#[derive(Clone, Copy, Dbg, PartialEq)]
+pub enum BenchOp {
+  Sm3,
+  Sm4Ecb,
+  Sm4Cbc,
+}
+
+impl BenchOp {
+  pub const fn name(self) -> &'static str {
+    match self {
+      Self::Sm3 => "SM3",
+      Self::Sm4Ecb => "SM4-ECB",
+      Self::Sm4Cbc => "SM4-CBC",
+    }
+  }
+
+  fn req(self, data: &[u8]) -> Bytes {
+    match self {
+      Self::Sm3 => sm3(data),
+      Self::Sm4Ecb => sm4_enc_ecb(data),
+      Self::Sm4Cbc => sm4_enc_cbc(data, &BENCH_IV),
+    }
+  }
+
+  fn expected(self, data: &[u8]) -> Vec<u8> {
+    match self {
+      Self::Sm3 => soft::sm3(data).to_vec(),
+      Self::Sm4Ecb => soft::sm4(&BENCH_KEY, true, BlockMode::ECB, &[0; IV_LEN], data),
+      Self::Sm4Cbc => soft::sm4(&BENCH_KEY, true, BlockMode::CBC, &BENCH_IV, data),
+    }
+  }
+}
+
+#[derive(Clone, Dbg)]
+pub struct BenchConf {
+  pub op: BenchOp,
+  pub frms: u32,
+  pub size: usize,
+  pub window: u32,
+  pub timeout: Durn,
+}
+
+#[derive(Clone, Dbg)]
+pub struct BenchRst {
+  pub conf: BenchConf,
+  pub at: SystemTime,
+  pub ok: u32,
+  pub bad: u32,
+  pub lost: u32,
+  pub elapsed: Durn,
+  pub device_mbps: f64,
+  pub bound_mbps: f64,
+}
+
+impl BenchRst {
+  pub fn utilisation(&self) -> f64 {
+    if 0.0 == self.bound_mbps {
+      0.0
+    } else {
+      self.device_mbps / self.bound_mbps
+    }
+  }
+
+  pub fn to_csv(&self, label: &str) -> Str {
+    let at = self.at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let label = label.replace([',', '\n'], " ");
+    f!(
+      "{label},{at},{},{},{},{},{},{},{},{:.6},{:.6},{:.6},{:.4}",
+      self.conf.op.name(),
+      self.conf.frms,
+      self.conf.size,
+      self.conf.window,
+      self.ok,
+      self.bad,
+      self.lost,
+      self.elapsed.as_secs_f64(),
+      self.device_mbps,
+      self.bound_mbps,
+      self.utilisation()
+    )
+  }
+
+  pub fn save(&self, path: &Path, label: &str) -> Rst<()> {
+    let is_new = !path.exists();
+    let mut file = match OpenOptions::new().create(true).append(true).open(path) {
+      Ok(file) => file,
+      Err(e) => {
+        we!("{}", mk_err_str(e, "测试结果文件打开失败"));
+      }
+    };
+    let header = if is_new { f!("{BENCH_CSV_HEADER}\n") } else { Str::new() };
+    if let Err(e) = writeln!(file, "{header}{}", self.to_csv(label)) {
+      we!("{}", mk_err_str(e, "测试结果写入失败"));
+    }
+    Ok(())
+  }
+}
+
+pub fn line_bits_per_byte(sp_conf: &SpConf) -> f64 {
+  let data_bits = match sp_conf.data_bits {
+    DataBits::Five => 5.0,
+    DataBits::Six => 6.0,
+    DataBits::Seven => 7.0,
+    DataBits::Eight => 8.0,
+  };
+  let parity = if Parity::None == sp_conf.parity { 0.0 } else { 1.0 };
+  let stop = if StopBits::Two == sp_conf.stop_bits { 2.0 } else { 1.0 };
+  (1.0 + data_bits + parity + stop) / 8.0
+}
+
+pub fn bound_mbps(conf: &BenchConf, sp_conf: &SpConf) -> f64 {
+  let req_len = conf.op.req(&vec![0; conf.size]).len();
+  let rsp_len = FRM_MIN_LEN + conf.op.expected(&vec![0; conf.size]).len();
+  let bytes_per_s = sp_conf.baud_rate as f64 / 8.0 / line_bits_per_byte(sp_conf);
+  bytes_per_s * conf.size as f64 * 8.0 / req_len.max(rsp_len) as f64 / 1e6
+}
+
+fn payload(idx: u32, size: usize) -> Vec<u8> {
+  (0..size).map(|i| (i as u32).wrapping_mul(31).wrapping_add(idx) as u8).collect()
+}
+
+pub(crate) fn with_timeout<T>(
+  sp: &mut dyn SerialPort,
+  timeout: Durn,
+  job: impl FnOnce(&mut dyn SerialPort) -> Rst<T>,
+) -> Rst<T> {
+  let prev = sp.timeout();
+  if let Err(e) = sp.set_timeout(timeout) {
+    we!("{}", mk_err_str(e, "串口参数设置失败"));
+  }
+  let rst = job(sp);
+  if let Err(e) = sp.set_timeout(prev) {
+    warn!("{}", mk_err_str(e, "串口超时恢复失败"));
+  }
+  rst
+}
+
+pub(crate) fn read_frms(
+  sp: &mut dyn SerialPort,
+  dec: &mut FrmDec,
+  hub: Opt<&dyn Sink>,
+) -> Rst<Vec<Bytes>> {
+  let mut buf = [0u8; 4096];
+  match sp.read(&mut buf) {
+    Ok(n) => {
+      if let Some(hub) = hub.filter(|_| 0 != n) {
+        hub.emit(&Evt::Rx(Bytes::copy_from_slice(&buf[..n])));
+      }
+      dec.push(&buf[..n]);
+    }
+    Err(e) if ErrorKind::TimedOut == e.kind() => {}
+    Err(e) => {
+      we!("{}", mk_err_str(e, "读取串口数据失败"));
+    }
+  }
+  Ok(dec.flatten().collect())
+}
+
+fn inject_key(sp: &mut dyn SerialPort, timeout: Durn) -> Rst<()> {
+  if let Err(e) = sp.write_all(&key(&BENCH_KEY)) {
+    we!("{}", mk_err_str(e, "测试密钥发送失败"));
+  }
+  let mut dec = FrmDec::default();
+  let deadline = Instant::now() + timeout;
+  while Instant::now() < deadline {
+    if read_frms(sp, &mut dec, None)?.iter().any(|frm| Ok(Rsp::Key) == Rsp::parse(frm)) {
+      return Ok(());
+    }
+  }
+  we!("测试密钥注入无响应");
+}
+
+pub fn run(sp: &mut dyn SerialPort, conf: &BenchConf, sp_conf: &SpConf) -> Rst<BenchRst> {
+  with_timeout(sp, conf.timeout, |sp| measure(sp, conf, sp_conf))
+}
+
+fn measure(sp: &mut dyn SerialPort, conf: &BenchConf, sp_conf: &SpConf) -> Rst<BenchRst> {
+  let _ = sp.clear(ClearBuffer::All);
+  if BenchOp::Sm3 != conf.op {
+    inject_key(sp, conf.timeout)?;
+  }
+
+  let mut dec = FrmDec::default();
+  let mut inflight = VecDeque::new();
+  let (mut next, mut ok, mut bad) = (0, 0, 0);
+  let at = SystemTime::now();
+  let start = Instant::now();
+  let mut progress = start;
+  while ok + bad < conf.frms {
+    while next < conf.frms && (0 == conf.window || inflight.len() < conf.window as usize) {
+      let data = payload(next, conf.size);
+      if let Err(e) = sp.write_all(&conf.op.req(&data)) {
+        we!("{}", mk_err_str(e, "测试帧发送失败"));
+      }
+      inflight.push_back(conf.op.expected(&data));
+      next += 1;
+    }
+    for frm in read_frms(sp, &mut dec, None)? {
+      let Some(expected) = inflight.pop_front() else {
+        debug!(frm = const_hex::encode(&frm), "多余的响应帧");
+        continue;
+      };
+      if frm.len() >= FRM_MIN_LEN && frm[FRM_HEAD_LEN..frm.len() - FRM_TAIL_LEN] == expected[..] {
+        ok += 1;
+      } else {
+        bad += 1;
+      }
+      progress = Instant::now();
+    }
+    if progress.elapsed() > conf.timeout {
+      break;
+    }
+  }
+  let elapsed = progress.saturating_duration_since(start);
+  let bits = ok as f64 * conf.size as f64 * 8.0;
+  let device_mbps = if elapsed.is_zero() { 0.0 } else { bits / elapsed.as_secs_f64() / 1e6 };
+  Ok(BenchRst {
+    conf: conf.clone(),
+    at,
+    ok,
+    bad,
+    lost: conf.frms - ok - bad,
+    elapsed,
+    device_mbps,
+    bound_mbps: bound_mbps(conf, sp_conf),
+  })
+}
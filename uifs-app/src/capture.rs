@@ -0,0 +1,182 @@
+use crate::event::{Evt, Sink};
+use crate::{f, mk_err_str, we, Dbg, Opt, Rst, Str};
+use bytes::Bytes;
+use core::time::Duration as Durn;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+pub const CAP_HEADER: &str = "# uifs capture v2: <unix 微秒> <DEV|PEER> <TX|RX> <HEX>";
+
+#[derive(Clone, Copy, Dbg, Default, PartialEq)]
+pub enum Port {
+  #[default]
+  Dev,
+  Peer,
+}
+
+impl Port {
+  pub const fn name(self) -> &'static str {
+    match self {
+      Self::Dev => "DEV",
+      Self::Peer => "PEER",
+    }
+  }
+}
+
+#[derive(Clone, Copy, Dbg, PartialEq)]
+pub enum Dir {
+  Tx,
+  Rx,
+}
+
+#[derive(Clone, Dbg, PartialEq)]
+pub struct Rec {
+  pub at: SystemTime,
+  pub port: Port,
+  pub dir: Dir,
+  pub data: Bytes,
+}
+
+impl Rec {
+  pub fn now(port: Port, dir: Dir, data: Bytes) -> Self {
+    Self { at: SystemTime::now(), port, dir, data }
+  }
+
+  pub fn micros(&self) -> u64 {
+    self.at.duration_since(UNIX_EPOCH).unwrap_or_default().as_micros() as u64
+  }
+
+  pub fn to_line(&self) -> Str {
+    let dir = match self.dir {
+      Dir::Tx => "TX",
+      Dir::Rx => "RX",
+    };
+    f!("{} {} {dir} {}", self.micros(), self.port.name(), const_hex::encode(&self.data))
+  }
+
+  pub fn parse_line(line: &str) -> Rst<Self> {
+    let fields: Vec<_> = line.split_whitespace().collect();
+    let (at, port, dir, data) = match fields[..] {
+      [at, dir, data] => (at, Port::Dev, dir, data),
+      [at, port, dir, data] => {
+        let port = match port {
+          "DEV" => Port::Dev,
+          "PEER" => Port::Peer,
+          _ => {
+            we!("未知端口：{port}");
+          }
+        };
+        (at, port, dir, data)
+      }
+      _ => {
+        we!("记录格式有误：{line}");
+      }
+    };
+    let at = match at.parse() {
+      Ok(us) => UNIX_EPOCH + Durn::from_micros(us),
+      Err(e) => {
+        we!("{}", mk_err_str(e, "无法解析时间戳"));
+      }
+    };
+    let dir = match dir {
+      "TX" => Dir::Tx,
+      "RX" => Dir::Rx,
+      _ => {
+        we!("未知方向：{dir}");
+      }
+    };
+    let data = match const_hex::decode(data) {
+      Ok(data) => data.into(),
+      Err(e) => {
+        we!("{}", mk_err_str(e, "无法解析数据"));
+      }
+    };
+    Ok(Self { at, port, dir, data })
+  }
+}
+
+pub fn load(path: &Path) -> Rst<Vec<Rec>> {
+  let text = match std::fs::read_to_string(path) {
+    Ok(text) => text,
+    Err(e) => {
+      we!("{}", mk_err_str(e, "抓包文件读取失败"));
+    }
+  };
+  text
+    .lines()
+    .map(str::trim)
+    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+    .map(Rec::parse_line)
+    .collect()
+}
+
+#[derive(Default)]
+pub struct Capture {
+  file: Mutex<Opt<BufWriter<File>>>,
+}
+
+impl Capture {
+  pub const fn new() -> Self {
+    Self { file: Mutex::new(None) }
+  }
+
+  pub fn start(&self, path: &Path) -> Rst<()> {
+    let mut file = match File::create(path) {
+      Ok(file) => BufWriter::new(file),
+      Err(e) => {
+        we!("{}", mk_err_str(e, "抓包文件创建失败"));
+      }
+    };
+    if let Err(e) = writeln!(file, "{CAP_HEADER}") {
+      we!("{}", mk_err_str(e, "抓包文件写入失败"));
+    }
+    self.file.lock().unwrap().replace(file);
+    Ok(())
+  }
+
+  pub fn stop(&self) {
+    if let Some(mut file) = self.file.lock().unwrap().take() {
+      if let Err(e) = file.flush() {
+        warn!("{}", mk_err_str(e, "抓包文件写入失败"));
+      }
+    }
+  }
+
+  pub fn is_active(&self) -> bool {
+    self.file.lock().unwrap().is_some()
+  }
+
+  fn record(&self, rec: &Rec) {
+    let mut file = self.file.lock().unwrap();
+    let Some(w) = file.as_mut() else { return };
+    if let Err(e) = writeln!(w, "{}", rec.to_line()).and_then(|_| w.flush()) {
+      warn!("{}", mk_err_str(e, "抓包文件写入失败，已停止记录"));
+      file.take();
+    }
+  }
+}
+
+pub struct CapTap<'c> {
+  cap: &'c Capture,
+  port: Port,
+}
+
+impl Capture {
+  pub const fn tap(&self, port: Port) -> CapTap<'_> {
+    CapTap { cap: self, port }
+  }
+}
+
+impl Sink for CapTap<'_> {
+  fn emit(&self, evt: &Evt) {
+    match evt {
+      Evt::Tx(data) => self.cap.record(&Rec::now(self.port, Dir::Tx, data.clone())),
+      Evt::Rx(data) => self.cap.record(&Rec::now(self.port, Dir::Rx, data.clone())),
+      _ => {}
+    }
+  }
+}
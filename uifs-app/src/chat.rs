@@ -0,0 +1,147 @@
+use crate::display::trim_zeros;
+use crate::{receiver, write_sp, AppWindow, Options, WEAK_APP};
+use bytes::Bytes;
+use core::cell::RefCell;
+use serialport::{SerialPort, SerialPortInfo};
+use slint::{invoke_from_event_loop, ComponentHandle, Weak};
+use std::sync::Mutex;
+use tracing::{info, warn};
+use uifs_app::conf::SpConf;
+use uifs_app::event::{Evt, Hub, Sink};
+use uifs_app::protocol::{sm4_dec_ecb, sm4_enc_ecb, Rsp};
+use uifs_app::{mk_err_str, slint_f, Opt, Str};
+
+thread_local! {
+  static PEER_LSN_HNDLR: RefCell<Opt<tokio::task::JoinHandle<()>>> = const { RefCell::new(None) };
+  static PEER_SP: RefCell<Opt<Box<dyn SerialPort>>> = const { RefCell::new(None) };
+  static PEER_SP_NAME: RefCell<Opt<Str>> = const { RefCell::new(None) };
+}
+
+pub static PEER_HUB: Hub = Hub::new();
+
+pub fn peer_name() -> Opt<Str> {
+  PEER_SP_NAME.with_borrow(Clone::clone)
+}
+
+pub fn open_peer(sel_sp: &SerialPortInfo, sp_conf: &SpConf) -> bool {
+  let peer_sp = match sp_conf.builder(sel_sp.port_name.as_str()).open() {
+    Ok(peer_sp) => peer_sp,
+    Err(e) => {
+      warn!(sel_sp = ?sel_sp, "{}", mk_err_str(e, "无法打开对端串口"));
+      return false;
+    }
+  };
+  let lsn_sp = match peer_sp.try_clone() {
+    Ok(lsn_sp) => lsn_sp,
+    Err(e) => {
+      warn!(sel_sp = ?sel_sp, "{}", mk_err_str(e, "无法复制串口句柄"));
+      return false;
+    }
+  };
+  close_peer();
+  info!(sel_sp = ?sel_sp, "成功打开对端串口");
+  PEER_SP.set(Some(peer_sp));
+  PEER_SP_NAME.set(Some(sel_sp.port_name.clone()));
+  let lsn_task = tokio::spawn(receiver::parse_sp(lsn_sp, &PEER_HUB));
+  PEER_LSN_HNDLR.set(Some(lsn_task));
+  WEAK_APP.with(|w| w.get().unwrap().unwrap().global::<Options>().set_peer_connected(true));
+  true
+}
+
+pub fn close_peer() {
+  PEER_LSN_HNDLR.with_borrow_mut(|hndlr| hndlr.take().inspect(|h| h.abort()));
+  PEER_SP.take();
+  let Some(name) = PEER_SP_NAME.take() else { return };
+  info!(name = name, "对端串口已关闭");
+  WEAK_APP.with(|w| w.get().unwrap().unwrap().global::<Options>().set_peer_connected(false));
+}
+
+pub fn write_peer(data: &[u8], desc: &str) -> bool {
+  PEER_SP.with_borrow_mut(|peer_sp| {
+    let Some(sp) = peer_sp.as_mut() else {
+      warn!("{desc}：对端串口未打开");
+      return false;
+    };
+    if let Err(e) = sp.write_all(data) {
+      warn!(peer_sp = ?peer_sp, "{}", mk_err_str(&e, desc));
+      if std::io::ErrorKind::TimedOut != e.kind() {
+        PEER_HUB.emit(&Evt::Disconnected(mk_err_str(e, desc)));
+      }
+      false
+    } else {
+      PEER_HUB.emit(&Evt::Tx(Bytes::copy_from_slice(data)));
+      true
+    }
+  })
+}
+
+fn write_side(bob: bool, data: &[u8], desc: &str) -> bool {
+  if bob {
+    write_peer(data, desc)
+  } else {
+    write_sp(data, desc)
+  }
+}
+
+fn side_name(bob: bool) -> &'static str {
+  if bob {
+    "Bob"
+  } else {
+    "Alice"
+  }
+}
+
+pub fn send(bob: bool, pt: &str) {
+  if write_side(bob, &sm4_enc_ecb(pt.as_bytes()), "ECB 加密请求发送失败") {
+    WEAK_APP.with(|w| {
+      let opts = w.get().unwrap().unwrap();
+      opts.global::<Options>().invoke_append_chat_text(bob, slint_f!("{}：{pt}", side_name(bob)));
+    });
+  }
+}
+
+pub struct ChatSink {
+  weak_app: Mutex<Weak<AppWindow>>,
+  bob: bool,
+}
+
+impl ChatSink {
+  pub fn new(weak_app: Weak<AppWindow>, bob: bool) -> Self {
+    Self { weak_app: Mutex::new(weak_app), bob }
+  }
+}
+
+impl Sink for ChatSink {
+  fn emit(&self, evt: &Evt) {
+    let evt = match evt {
+      Evt::Rsp(Rsp::Sm4Enc(..) | Rsp::Sm4Dec(..)) => evt.clone(),
+      Evt::Rsp(Rsp::Key) | Evt::Disconnected(_) if self.bob => evt.clone(),
+      _ => return,
+    };
+    let weak_app = self.weak_app.lock().unwrap().clone();
+    let bob = self.bob;
+    invoke_from_event_loop(move || {
+      let app = weak_app.unwrap();
+      let opts = app.global::<Options>();
+      if !opts.get_chat() || !opts.get_peer_connected() {
+        return;
+      }
+      let line = match evt {
+        Evt::Rsp(Rsp::Key) => slint_f!("密钥注入成功"),
+        Evt::Rsp(Rsp::Sm4Enc(_, ct)) => {
+          if !write_side(!bob, &sm4_dec_ecb(&ct), "密文转发失败") {
+            return;
+          }
+          slint_f!("密文：{}", const_hex::encode(&ct))
+        }
+        Evt::Rsp(Rsp::Sm4Dec(_, pt)) => {
+          slint_f!("{}：{}", side_name(!bob), String::from_utf8_lossy(trim_zeros(&pt)))
+        }
+        Evt::Disconnected(e) => slint_f!("串口连接中断：{e}"),
+        _ => return,
+      };
+      opts.invoke_append_chat_text(bob, line);
+    })
+    .unwrap();
+  }
+}
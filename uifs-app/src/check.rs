@@ -0,0 +1,29 @@
+use crate::{f, Rst, Str, BLK_LEN, IV_LEN, TX_MSG_MAX_LEN};
+
+pub fn blk(data: Vec<u8>) -> Rst<[u8; BLK_LEN], Str> {
+  let len = data.len();
+  data.try_into().map_err(|_| f!("长度须为 128 位（{BLK_LEN} 字节），实际 {len} 字节"))
+}
+
+pub fn len(data: Vec<u8>, extra: usize) -> Rst<Vec<u8>, Str> {
+  let max = TX_MSG_MAX_LEN - extra;
+  if data.len() > max {
+    return Err(f!("过长：{} 字节，上限 {max} 字节", data.len()));
+  }
+  Ok(data)
+}
+
+pub fn pt(data: Vec<u8>, cbc: bool) -> Rst<Vec<u8>, Str> {
+  if data.is_empty() {
+    return Err("不能为空".into());
+  }
+  len(data, if cbc { IV_LEN } else { 0 })
+}
+
+pub fn ct(data: Vec<u8>, cbc: bool) -> Rst<Vec<u8>, Str> {
+  let data = pt(data, cbc)?;
+  if 0 != data.len() % BLK_LEN {
+    return Err(f!("长度须为 {BLK_LEN} 字节的整数倍，实际 {} 字节", data.len()));
+  }
+  Ok(data)
+}
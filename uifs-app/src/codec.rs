@@ -0,0 +1,135 @@
+use crate::{f, we, Dbg, Opt, Rst, Str};
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+#[derive(Clone, Copy, Dbg, PartialEq)]
+pub enum Codec {
+  Utf8,
+  Hex,
+  Base64,
+  CArray,
+}
+
+fn strip_ws(s: &str) -> Str {
+  s.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+fn c_byte(tok: &str) -> Opt<u8> {
+  let tok = tok.trim();
+  if let Some(hex) = tok.strip_prefix("0x").or_else(|| tok.strip_prefix("0X")) {
+    u8::from_str_radix(hex, 16).ok()
+  } else if let Some(bin) = tok.strip_prefix("0b").or_else(|| tok.strip_prefix("0B")) {
+    u8::from_str_radix(bin, 2).ok()
+  } else if let Some(c) = tok.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')) {
+    let mut chars = c.chars();
+    match (chars.next(), chars.next()) {
+      (Some(c), None) if c.is_ascii() => Some(c as u8),
+      _ => None,
+    }
+  } else {
+    tok.parse().ok()
+  }
+}
+
+impl Codec {
+  pub const fn from_idx(idx: i32) -> Self {
+    match idx {
+      1 => Self::Hex,
+      2 => Self::Base64,
+      3 => Self::CArray,
+      _ => Self::Utf8,
+    }
+  }
+
+  pub const fn name(self) -> &'static str {
+    match self {
+      Self::Utf8 => "UTF-8",
+      Self::Hex => "HEX",
+      Self::Base64 => "Base64",
+      Self::CArray => "C 数组",
+    }
+  }
+
+  pub fn decode(self, s: &str) -> Rst<Vec<u8>> {
+    match self {
+      Self::Utf8 => Ok(s.as_bytes().to_vec()),
+      Self::Hex => match const_hex::decode(strip_ws(s)) {
+        Ok(data) => Ok(data),
+        Err(e) => {
+          we!("输入不是合法的十六进制：{e}");
+        }
+      },
+      Self::Base64 => match STANDARD.decode(strip_ws(s)) {
+        Ok(data) => Ok(data),
+        Err(e) => {
+          we!("输入不是合法的 Base64：{e}");
+        }
+      },
+      Self::CArray => {
+        let body = match (s.find('{'), s.rfind('}')) {
+          (Some(l), Some(r)) if l < r => &s[l + 1..r],
+          _ => s,
+        };
+        let toks = body.split(',').map(str::trim).filter(|tok| !tok.is_empty());
+        toks
+          .map(|tok| match c_byte(tok) {
+            Some(b) => Ok(b),
+            None => {
+              we!("C 数组元素无效：{tok}");
+            }
+          })
+          .collect()
+      }
+    }
+  }
+
+  pub fn encode(self, data: &[u8]) -> Str {
+    match self {
+      Self::Utf8 => String::from_utf8_lossy(data).into_owned(),
+      Self::Hex => const_hex::encode(data),
+      Self::Base64 => STANDARD.encode(data),
+      Self::CArray => {
+        let bytes: Vec<_> = data.iter().map(|b| f!("0x{b:02x}")).collect();
+        f!("{{{}}}", bytes.join(", "))
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const ALL: [Codec; 4] = [Codec::Utf8, Codec::Hex, Codec::Base64, Codec::CArray];
+
+  #[test]
+  fn round_trip() {
+    let data = b"uifs \x00\xff";
+    for codec in ALL {
+      let data = if Codec::Utf8 == codec { &data[..5] } else { &data[..] };
+      assert_eq!(data, codec.decode(&codec.encode(data)).unwrap(), "{}", codec.name());
+    }
+  }
+
+  #[test]
+  fn decode_ignores_ws() {
+    assert_eq!(vec![0xde, 0xad, 0xbe, 0xef], Codec::Hex.decode(" de ad\nbe\tef ").unwrap());
+    assert_eq!(b"uifs".to_vec(), Codec::Base64.decode("dWl m\ncw==").unwrap());
+  }
+
+  #[test]
+  fn decode_c_array() {
+    let data = Codec::CArray.decode("uint8_t k[] = { 0x1F, 0b101, 'A', 200, };").unwrap();
+    assert_eq!(vec![0x1f, 0b101, b'A', 200], data);
+    assert_eq!(vec![1, 2], Codec::CArray.decode("1, 2").unwrap());
+    assert_eq!("{0x01, 0xab}", Codec::CArray.encode(&[1, 0xab]));
+  }
+
+  #[test]
+  fn decode_errs() {
+    assert!(Codec::Hex.decode("abc").is_err());
+    assert!(Codec::Hex.decode("zz").is_err());
+    assert!(Codec::Base64.decode("@@@@").is_err());
+    assert!(Codec::CArray.decode("{0x100}").is_err());
+    assert!(Codec::CArray.decode("{'ab'}").is_err());
+  }
+}
@@ -0,0 +1,163 @@
+use crate::{mk_err_str, we, Dbg, Opt, Rst, Str, SP_BAUD_RATE, SP_TIMEOUT};
+use core::time::Duration as Durn;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serialport::{DataBits, FlowControl, Parity, SerialPort, SerialPortBuilder, StopBits};
+use std::path::PathBuf;
+use tracing::warn;
+
+pub const LOG_DIR_ENV: &str = "UIFS_LOG_DIR";
+pub const DIS_LOG_FILE_ENV: &str = "UIFS_DIS_LOG_FILE";
+pub const ENBL_LOG_CNSL_ENV: &str = "UIFS_ENBL_LOG_CNSL";
+
+pub fn conf_dir() -> Opt<PathBuf> {
+  dirs::config_dir().map(|dir| dir.join("uifs"))
+}
+
+pub fn try_load<T: DeserializeOwned + Default>(name: &str) -> Rst<T> {
+  let Some(path) = conf_dir().map(|dir| dir.join(name)) else { return Ok(T::default()) };
+  let text = match std::fs::read_to_string(&path) {
+    Ok(text) => text,
+    Err(e) if std::io::ErrorKind::NotFound == e.kind() => return Ok(T::default()),
+    Err(e) => {
+      we!("{} path={path:?}", mk_err_str(e, "配置文件读取失败"));
+    }
+  };
+  match toml::from_str(&text) {
+    Ok(conf) => Ok(conf),
+    Err(e) => {
+      we!("{} path={path:?}", mk_err_str(e, "配置文件解析失败"));
+    }
+  }
+}
+
+pub fn load<T: DeserializeOwned + Default>(name: &str) -> T {
+  try_load(name).unwrap_or_else(|e| {
+    warn!("{e}");
+    T::default()
+  })
+}
+
+pub fn save<T: Serialize>(name: &str, conf: &T) -> Rst<()> {
+  let Some(dir) = conf_dir() else {
+    we!("无法确定配置目录");
+  };
+  if let Err(e) = std::fs::create_dir_all(&dir) {
+    we!("{}", mk_err_str(e, "配置目录创建失败"));
+  }
+  let text = match toml::to_string_pretty(conf) {
+    Ok(text) => text,
+    Err(e) => {
+      we!("{}", mk_err_str(e, "配置序列化失败"));
+    }
+  };
+  if let Err(e) = std::fs::write(dir.join(name), text) {
+    we!("{}", mk_err_str(e, "配置文件写入失败"));
+  }
+  Ok(())
+}
+
+#[derive(Clone, Dbg, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpConf {
+  pub baud_rate: u32,
+  pub data_bits: DataBits,
+  pub parity: Parity,
+  pub stop_bits: StopBits,
+  pub flow_ctrl: FlowControl,
+  pub timeout_ms: u64,
+}
+
+impl Default for SpConf {
+  fn default() -> Self {
+    Self {
+      baud_rate: SP_BAUD_RATE,
+      data_bits: DataBits::Eight,
+      parity: Parity::None,
+      stop_bits: StopBits::One,
+      flow_ctrl: FlowControl::None,
+      timeout_ms: SP_TIMEOUT.as_millis() as u64,
+    }
+  }
+}
+
+impl SpConf {
+  pub const FILE: &'static str = "serial.toml";
+
+  pub fn builder(&self, path: &str) -> SerialPortBuilder {
+    serialport::new(path, self.baud_rate)
+      .data_bits(self.data_bits)
+      .parity(self.parity)
+      .stop_bits(self.stop_bits)
+      .flow_control(self.flow_ctrl)
+      .timeout(Durn::from_millis(self.timeout_ms))
+  }
+
+  pub fn apply(&self, sp: &mut dyn SerialPort) -> serialport::Result<()> {
+    sp.set_baud_rate(self.baud_rate)?;
+    sp.set_data_bits(self.data_bits)?;
+    sp.set_parity(self.parity)?;
+    sp.set_stop_bits(self.stop_bits)?;
+    sp.set_flow_control(self.flow_ctrl)?;
+    sp.set_timeout(Durn::from_millis(self.timeout_ms))
+  }
+}
+
+#[derive(Clone, Dbg, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConf {
+  pub log_dir: Str,
+  pub log_to_file: bool,
+  pub log_to_cnsl: bool,
+  pub save_session: bool,
+  pub sp_name: Opt<Str>,
+  pub key_name: Opt<Str>,
+  pub op: i32,
+  pub mode: i32,
+  pub msg_enc: i32,
+  pub key_enc: i32,
+  pub iv_enc: i32,
+  pub result_enc: i32,
+  pub timeout_ms: i32,
+  pub retries: i32,
+  pub auto_reconnect: bool,
+}
+
+impl Default for AppConf {
+  fn default() -> Self {
+    Self {
+      log_dir: Str::from("./log"),
+      log_to_file: true,
+      log_to_cnsl: false,
+      save_session: true,
+      sp_name: None,
+      key_name: None,
+      op: 0,
+      mode: 0,
+      msg_enc: 0,
+      key_enc: 1,
+      iv_enc: 1,
+      result_enc: 0,
+      timeout_ms: 1000,
+      retries: 0,
+      auto_reconnect: false,
+    }
+  }
+}
+
+impl AppConf {
+  pub const FILE: &'static str = "settings.toml";
+
+  pub fn with_env(&self) -> Self {
+    let mut conf = self.clone();
+    if let Ok(dir) = std::env::var(LOG_DIR_ENV) {
+      conf.log_dir = dir;
+    }
+    if std::env::var_os(DIS_LOG_FILE_ENV).is_some() {
+      conf.log_to_file = false;
+    }
+    if std::env::var_os(ENBL_LOG_CNSL_ENV).is_some() {
+      conf.log_to_cnsl = true;
+    }
+    conf
+  }
+}
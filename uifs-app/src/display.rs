@@ -0,0 +1,150 @@
+use crate::{AppWindow, Options};
+use core::time::Duration as Durn;
+use slint::{invoke_from_event_loop, ComponentHandle, Weak};
+use std::sync::Mutex;
+use uifs_app::{
+  capture::Dir,
+  codec::Codec,
+  echo::EchoView,
+  event::{Evt, Sink},
+  f,
+  inspect::{fields, Field},
+  obsr::Obsr,
+  protocol::{BlockMode, OpFlag, Rsp},
+  slint_f, Opt, IV_LEN,
+};
+
+pub fn trim_zeros(data: &[u8]) -> &[u8] {
+  let end = data.iter().rposition(|&b| 0 != b).map_or(0, |i| i + 1);
+  &data[..end]
+}
+
+fn show(opts: &Options<'_>, data: &[u8], auto: Codec) -> String {
+  match opts.get_result_enc() {
+    0 => auto.encode(data),
+    idx => Codec::from_idx(idx - 1).encode(data),
+  }
+}
+
+pub fn op_name(op: OpFlag) -> &'static str {
+  match op {
+    OpFlag::Key => "密钥注入",
+    OpFlag::Sm3 => "SM3 散列",
+    OpFlag::Sm4Enc => "SM4 加密",
+    OpFlag::Sm4Dec => "SM4 解密",
+  }
+}
+
+pub struct AppSink {
+  weak_app: Mutex<Weak<AppWindow>>,
+  rtt: Mutex<Opt<(OpFlag, Durn)>>,
+  iv: Mutex<Opt<[u8; IV_LEN]>>,
+  live: bool,
+}
+
+fn cbc_iv(frm: &[u8]) -> Opt<[u8; IV_LEN]> {
+  let (_, range) = fields(Dir::Tx, frm).into_iter().find(|&(fld, _)| Field::Iv == fld)?;
+  frm[range].try_into().ok()
+}
+
+impl AppSink {
+  pub fn new(weak_app: Weak<AppWindow>) -> Self {
+    Self { weak_app: Mutex::new(weak_app), rtt: Mutex::new(None), iv: Mutex::new(None), live: true }
+  }
+
+  pub fn replay(weak_app: Weak<AppWindow>) -> Self {
+    Self { live: false, ..Self::new(weak_app) }
+  }
+}
+
+impl Sink for AppSink {
+  fn emit(&self, evt: &Evt) {
+    let (rtt, iv) = match evt {
+      Evt::Rtt { op, rtt } => {
+        self.rtt.lock().unwrap().replace((*op, *rtt));
+        return;
+      }
+      Evt::Tx(frm) => {
+        if let Some(iv) = cbc_iv(frm) {
+          self.iv.lock().unwrap().replace(iv);
+        }
+        return;
+      }
+      Evt::Rsp(rsp) => {
+        let mut rtt = self.rtt.lock().unwrap();
+        let rtt = if rtt.is_some_and(|(op, _)| rsp.op() == op) { rtt.take() } else { None };
+        let iv = match rsp {
+          Rsp::Sm4Enc(BlockMode::CBC, _) | Rsp::Sm4Dec(BlockMode::CBC, _) => {
+            self.iv.lock().unwrap().take()
+          }
+          _ => None,
+        };
+        (rtt, iv)
+      }
+      _ => (None, None),
+    };
+    let weak_app = self.weak_app.lock().unwrap().clone();
+    let evt = evt.clone();
+    let live = self.live;
+    invoke_from_event_loop(move || {
+      let app = weak_app.unwrap();
+      let opts = app.global::<Options>();
+      let name = if opts.get_name() { "Alice" } else { "Bob" };
+      let iv = match iv {
+        Some(iv) => f!("，IV：{}", Codec::from_idx(opts.get_iv_enc()).encode(&iv)),
+        None => String::new(),
+      };
+      let line = match evt {
+        Evt::Rsp(Rsp::Key) => {
+          if live {
+            opts.set_key_ready(true);
+          }
+          slint_f!("密钥注入成功")
+        }
+        Evt::Rsp(Rsp::Sm3(hash)) => slint_f!("SM3 结果：{}", show(&opts, &hash, Codec::Hex)),
+        Evt::Rsp(Rsp::Sm4Enc(mode, ct)) => {
+          let ct = trim_zeros(&ct);
+          if opts.get_chat() {
+            slint_f!("{name}：{}", String::from_utf8_lossy(ct))
+          } else if BlockMode::CBC == mode {
+            slint_f!("SM4 加密结果（CBC模式{iv}）：{}", show(&opts, ct, Codec::Hex))
+          } else {
+            slint_f!("SM4 加密结果（ECB模式）：{}", show(&opts, ct, Codec::Hex))
+          }
+        }
+        Evt::Rsp(Rsp::Sm4Dec(mode, pt)) => {
+          let pt = trim_zeros(&pt);
+          if opts.get_chat() {
+            let pt = String::from_utf8_lossy(pt);
+            slint_f!("{name}：{pt}")
+          } else if BlockMode::CBC == mode {
+            slint_f!("SM4 解密结果（CBC模式{iv}）：{}", show(&opts, pt, Codec::Utf8))
+          } else {
+            slint_f!("SM4 解密结果（ECB模式）：{}", show(&opts, pt, Codec::Utf8))
+          }
+        }
+        Evt::Echo(data) => {
+          slint_f!("回显：{}", EchoView::from_idx(opts.get_echo_view()).render(&data))
+        }
+        Evt::Obsr(data) => match Obsr::parse(&data) {
+          Some(obsr) => slint_f!("观测：{}", obsr.summary()),
+          None => slint_f!("观测：{}", String::from_utf8_lossy(&data)),
+        },
+        Evt::Resend { op, tries } => slint_f!("{} 请求无响应，第 {tries} 次重发", op_name(op)),
+        Evt::Timeout { op, waited } => {
+          slint_f!("{} 请求无响应：已等待 {} ms", op_name(op), waited.as_millis())
+        }
+        Evt::Tx(_) | Evt::Rx(_) | Evt::Skipped(_) | Evt::FrmErr(_) | Evt::RxErr(_) => return,
+        Evt::Disconnected(_) => return,
+        Evt::Rtt { .. } => return,
+      };
+      match rtt {
+        Some((_, rtt)) => {
+          opts.invoke_append_dp_text(slint_f!("{line}（{:.3} ms）", rtt.as_secs_f64() * 1e3))
+        }
+        None => opts.invoke_append_dp_text(line),
+      }
+    })
+    .unwrap();
+  }
+}
@@ -0,0 +1,133 @@
+use crate::{f, Dbg, Opt, Str};
+use bytes::Bytes;
+use core::fmt::Write;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+#[derive(Clone, Copy, Dbg, PartialEq)]
+pub enum EchoView {
+  Text,
+  Hex,
+  Mixed,
+}
+
+impl EchoView {
+  pub const fn from_idx(idx: i32) -> Self {
+    match idx {
+      1 => Self::Hex,
+      2 => Self::Mixed,
+      _ => Self::Text,
+    }
+  }
+
+  pub fn render(self, data: &[u8]) -> Str {
+    match self {
+      Self::Text => String::from_utf8_lossy(data).into_owned(),
+      Self::Hex => hex_spaced(data),
+      Self::Mixed => data.chunks(16).enumerate().fold(Str::new(), |mut out, (i, row)| {
+        let ascii: Str = row
+          .iter()
+          .map(|&b| if b.is_ascii_graphic() || b' ' == b { b as char } else { '.' })
+          .collect();
+        let _ = write!(out, "\n{:04x}  {:<47}  |{ascii}|", i * 16, hex_spaced(row));
+        out
+      }),
+    }
+  }
+}
+
+#[derive(Clone, Copy, Dbg, PartialEq)]
+pub enum LineEnd {
+  None,
+  Cr,
+  Lf,
+  CrLf,
+}
+
+impl LineEnd {
+  pub const fn from_idx(idx: i32) -> Self {
+    match idx {
+      1 => Self::Cr,
+      2 => Self::Lf,
+      3 => Self::CrLf,
+      _ => Self::None,
+    }
+  }
+
+  pub const fn bytes(self) -> &'static [u8] {
+    match self {
+      Self::None => b"",
+      Self::Cr => b"\r",
+      Self::Lf => b"\n",
+      Self::CrLf => b"\r\n",
+    }
+  }
+}
+
+pub fn hex_spaced(data: &[u8]) -> Str {
+  data.iter().map(|b| f!("{b:02x}")).collect::<Vec<_>>().join(" ")
+}
+
+#[derive(Clone, Dbg)]
+pub struct Check {
+  pub offset: usize,
+  pub got: Bytes,
+  pub expected: Vec<Opt<u8>>,
+}
+
+impl Check {
+  pub fn mismatches(&self) -> usize {
+    self.got.iter().zip(&self.expected).filter(|(&g, &e)| Some(g) != e).count()
+  }
+
+  pub fn first_mismatch(&self) -> Opt<usize> {
+    let i = self.got.iter().zip(&self.expected).position(|(&g, &e)| Some(g) != e)?;
+    Some(self.offset + i)
+  }
+
+  pub fn marked(&self) -> Str {
+    let marks: Vec<_> = self
+      .got
+      .iter()
+      .zip(&self.expected)
+      .map(|(&g, &e)| match e {
+        Some(e) if g == e => f!("{g:02x}"),
+        Some(e) => f!("[{g:02x}≠{e:02x}]"),
+        None => f!("[{g:02x}≠--]"),
+      })
+      .collect();
+    marks.join(" ")
+  }
+}
+
+#[derive(Default)]
+pub struct Loopback {
+  state: Mutex<(VecDeque<u8>, usize)>,
+}
+
+impl Loopback {
+  pub const fn new() -> Self {
+    Self { state: Mutex::new((VecDeque::new(), 0)) }
+  }
+
+  pub fn expect(&self, sent: &[u8]) -> usize {
+    let mut state = self.state.lock().unwrap();
+    let missing = state.0.len();
+    state.0.clear();
+    state.0.extend(sent);
+    state.1 = 0;
+    missing
+  }
+
+  pub fn reset(&self) {
+    self.expect(&[]);
+  }
+
+  pub fn check(&self, got: Bytes) -> Check {
+    let mut state = self.state.lock().unwrap();
+    let offset = state.1;
+    let expected = got.iter().map(|_| state.0.pop_front()).collect();
+    state.1 += got.len();
+    Check { offset, got, expected }
+  }
+}
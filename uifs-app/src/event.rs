@@ -0,0 +1,83 @@
+use crate::protocol::{FrmErr, OpFlag, Rsp};
+use crate::{Dbg, Str};
+use bytes::Bytes;
+use core::time::Duration as Durn;
+use std::sync::{Arc, RwLock};
+use tracing::{debug, info, warn};
+
+#[derive(Clone, Dbg)]
+pub enum Evt {
+  Tx(Bytes),
+  Rx(Bytes),
+  Skipped(usize),
+  Rsp(Rsp),
+  Echo(Bytes),
+  Obsr(Bytes),
+  FrmErr(FrmErr),
+  RxErr(Str),
+  Disconnected(Str),
+  Resend { op: OpFlag, tries: u32 },
+  Timeout { op: OpFlag, waited: Durn },
+  Rtt { op: OpFlag, rtt: Durn },
+}
+
+pub trait Sink: Send + Sync {
+  fn emit(&self, evt: &Evt);
+}
+
+impl<S: Sink + ?Sized> Sink for &S {
+  fn emit(&self, evt: &Evt) {
+    (**self).emit(evt)
+  }
+}
+
+#[derive(Default)]
+pub struct Hub {
+  sinks: RwLock<Vec<Arc<dyn Sink>>>,
+}
+
+impl Hub {
+  pub const fn new() -> Self {
+    Self { sinks: RwLock::new(Vec::new()) }
+  }
+
+  pub fn subscribe(&self, sink: Arc<dyn Sink>) {
+    self.sinks.write().unwrap().push(sink);
+  }
+}
+
+impl Sink for Hub {
+  fn emit(&self, evt: &Evt) {
+    let sinks = self.sinks.read().unwrap().clone();
+    sinks.iter().for_each(|sink| sink.emit(evt));
+  }
+}
+
+pub struct FnSink<F>(pub F);
+
+impl<F: Fn(&Evt) + Send + Sync> Sink for FnSink<F> {
+  fn emit(&self, evt: &Evt) {
+    (self.0)(evt)
+  }
+}
+
+pub struct LogSink;
+
+impl Sink for LogSink {
+  fn emit(&self, evt: &Evt) {
+    match evt {
+      Evt::Tx(data) => debug!(data = const_hex::encode(data), "已发送数据"),
+      Evt::Rx(data) => debug!(data = const_hex::encode(data), "已读取数据"),
+      Evt::Skipped(n) => debug!(n = n, "跳过非帧数据"),
+      Evt::Rsp(rsp) => info!(rsp = ?rsp, "收到响应"),
+      Evt::Echo(data) => info!(data = const_hex::encode(data), "收到回显"),
+      Evt::Obsr(data) => info!(data = const_hex::encode(data), "收到观测数据"),
+      Evt::FrmErr(e) => warn!("{e}"),
+      Evt::RxErr(e) => warn!("{e}"),
+      Evt::Disconnected(e) => warn!("串口连接中断：{e}"),
+      Evt::Resend { op, tries } => info!(op = ?op, tries = tries, "请求无响应，重发"),
+      Evt::Timeout { op, waited } => warn!(op = ?op, waited = ?waited, "请求超时"),
+      Evt::Rtt { op, rtt } => info!(op = ?op, rtt = ?rtt, "往返时延"),
+    }
+  }
+}
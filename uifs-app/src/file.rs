@@ -0,0 +1,244 @@
+use crate::bench::{read_frms, with_timeout};
+use crate::event::{Evt, Sink};
+use crate::protocol::{
+  sm3, sm4_dec_cbc, sm4_dec_ecb, sm4_enc_cbc, sm4_enc_ecb, BlockMode, FrmDec, Rsp,
+};
+use crate::{mk_err_str, we, Dbg, Opt, Rst, BLK_LEN, IV_LEN, SM3_HASH_LEN, TX_MSG_MAX_LEN};
+use bytes::Bytes;
+use core::sync::atomic::{AtomicBool, Ordering::Relaxed};
+use core::time::Duration as Durn;
+use serialport::{ClearBuffer, SerialPort};
+use std::fs::File;
+use std::io::{BufWriter, ErrorKind, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+pub const FILE_CHUNK: usize = 4096;
+
+#[derive(Clone, Copy, Dbg, PartialEq)]
+pub enum FileOp {
+  Sm3,
+  Sm4Enc(BlockMode),
+  Sm4Dec(BlockMode),
+}
+
+#[derive(Clone, Dbg)]
+pub struct FileJob {
+  pub op: FileOp,
+  pub input: PathBuf,
+  pub output: Opt<PathBuf>,
+  pub iv: [u8; IV_LEN],
+  pub timeout: Durn,
+}
+
+#[derive(Clone, Dbg, PartialEq)]
+pub enum FileRst {
+  Hash([u8; SM3_HASH_LEN]),
+  Written(u64),
+  Cancelled(u64),
+}
+
+fn pad(data: &mut Vec<u8>) {
+  let n = BLK_LEN - data.len() % BLK_LEN;
+  data.resize(data.len() + n, n as u8);
+}
+
+fn unpad(data: &[u8]) -> Opt<&[u8]> {
+  let &n = data.last()?;
+  let n = n as usize;
+  if !(1..=BLK_LEN).contains(&n) || n > data.len() {
+    return None;
+  }
+  let (pt, padding) = data.split_at(data.len() - n);
+  padding.iter().all(|&b| n == b as usize).then_some(pt)
+}
+
+fn next_iv(enc: bool, req: &[u8], got: &[u8]) -> [u8; IV_LEN] {
+  let ct = if enc { got } else { req };
+  ct[ct.len() - BLK_LEN..].try_into().unwrap()
+}
+
+fn request(
+  sp: &mut dyn SerialPort,
+  hub: &dyn Sink,
+  dec: &mut FrmDec,
+  req: &Bytes,
+  timeout: Durn,
+) -> Rst<Rsp> {
+  if let Err(e) = sp.write_all(req) {
+    if ErrorKind::TimedOut != e.kind() {
+      hub.emit(&Evt::Disconnected(mk_err_str(&e, "请求发送失败")));
+    }
+    we!("{}", mk_err_str(e, "请求发送失败"));
+  }
+  hub.emit(&Evt::Tx(req.clone()));
+  let deadline = Instant::now() + timeout;
+  loop {
+    if let Some(frm) = read_frms(sp, dec, Some(hub))?.first() {
+      return match Rsp::parse(frm) {
+        Ok(rsp) => Ok(rsp),
+        Err(e) => {
+          we!("响应帧有误：{e}");
+        }
+      };
+    }
+    if Instant::now() > deadline {
+      we!("设备无响应：已等待 {} ms", timeout.as_millis());
+    }
+  }
+}
+
+fn create(output: &Opt<PathBuf>) -> Rst<BufWriter<File>> {
+  let Some(path) = output else {
+    we!("未指定输出文件");
+  };
+  match File::create(path) {
+    Ok(file) => Ok(BufWriter::new(file)),
+    Err(e) => {
+      we!("{}", mk_err_str(e, "输出文件创建失败"));
+    }
+  }
+}
+
+fn write(out: &mut impl Write, data: &[u8]) -> Rst<()> {
+  if let Err(e) = out.write_all(data) {
+    we!("{}", mk_err_str(e, "输出文件写入失败"));
+  }
+  Ok(())
+}
+
+pub fn run(
+  sp: &mut dyn SerialPort,
+  job: &FileJob,
+  hub: &dyn Sink,
+  cancel: &AtomicBool,
+  progress: impl FnMut(usize, usize),
+) -> Rst<FileRst> {
+  let data = match std::fs::read(&job.input) {
+    Ok(data) => data,
+    Err(e) => {
+      we!("{}", mk_err_str(e, "输入文件读取失败"));
+    }
+  };
+  with_timeout(sp, Durn::from_millis(10), |sp| process(sp, job, data, hub, cancel, progress))
+}
+
+fn process(
+  sp: &mut dyn SerialPort,
+  job: &FileJob,
+  mut data: Vec<u8>,
+  hub: &dyn Sink,
+  cancel: &AtomicBool,
+  mut progress: impl FnMut(usize, usize),
+) -> Rst<FileRst> {
+  let _ = sp.clear(ClearBuffer::All);
+  let mut dec = FrmDec::default();
+
+  let (enc, mode) = match job.op {
+    FileOp::Sm3 => {
+      if data.len() > TX_MSG_MAX_LEN {
+        we!("SM3 仅支持单帧，文件不能超过 {TX_MSG_MAX_LEN} 字节");
+      }
+      let Rsp::Sm3(hash) = request(sp, hub, &mut dec, &sm3(&data), job.timeout)? else {
+        we!("响应操作不符");
+      };
+      progress(data.len(), data.len());
+      if job.output.is_some() {
+        let mut out = create(&job.output)?;
+        write(&mut out, const_hex::encode(hash).as_bytes())?;
+      }
+      return Ok(FileRst::Hash(hash));
+    }
+    FileOp::Sm4Enc(mode) => (true, mode),
+    FileOp::Sm4Dec(mode) => (false, mode),
+  };
+  if enc {
+    pad(&mut data);
+  } else if 0 != data.len() % BLK_LEN || data.is_empty() {
+    we!("密文长度须为 {BLK_LEN} 字节的正整数倍");
+  }
+
+  let mut out = create(&job.output)?;
+  let total = data.len();
+  let mut iv = job.iv;
+  let (mut done, mut written) = (0, 0);
+  for chunk in data.chunks(FILE_CHUNK) {
+    if cancel.load(Relaxed) {
+      return Ok(FileRst::Cancelled(written as u64));
+    }
+    let req = match (enc, mode) {
+      (true, BlockMode::ECB) => sm4_enc_ecb(chunk),
+      (true, BlockMode::CBC) => sm4_enc_cbc(chunk, &iv),
+      (false, BlockMode::ECB) => sm4_dec_ecb(chunk),
+      (false, BlockMode::CBC) => sm4_dec_cbc(chunk, &iv),
+    };
+    let got: Bytes = match request(sp, hub, &mut dec, &req, job.timeout)? {
+      Rsp::Sm4Enc(_, got) if enc => got,
+      Rsp::Sm4Dec(_, got) if !enc => got,
+      _ => {
+        we!("响应操作不符");
+      }
+    };
+    if chunk.len() != got.len() {
+      we!("响应长度不符：期望 {}，实际 {}", chunk.len(), got.len());
+    }
+    iv = next_iv(enc, chunk, &got);
+    done += chunk.len();
+    let got = if !enc && total == done {
+      let Some(pt) = unpad(&got) else {
+        we!("填充无效，请检查密钥与初始向量");
+      };
+      pt
+    } else {
+      &got[..]
+    };
+    write(&mut out, got)?;
+    written += got.len();
+    progress(done, total);
+  }
+  if let Err(e) = out.flush() {
+    we!("{}", mk_err_str(e, "输出文件写入失败"));
+  }
+  Ok(FileRst::Written(written as u64))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::soft;
+
+  #[test]
+  fn pkcs7() {
+    for len in [0, 1, 15, 16, 17] {
+      let mut data = vec![0xAA; len];
+      pad(&mut data);
+      assert_eq!(0, data.len() % BLK_LEN);
+      assert!(data.len() > len);
+      assert_eq!(Some(&[0xAA; 17][..len]), unpad(&data));
+    }
+    assert_eq!(None, unpad(&[]));
+    assert_eq!(None, unpad(&[0; 16]));
+    assert_eq!(None, unpad(&[17; 17]));
+    assert_eq!(None, unpad(&[[1; 14].as_slice(), &[3, 2]].concat()));
+  }
+
+  #[test]
+  fn cbc_chaining() {
+    let key = [0x42; 16];
+    let iv0 = [0x24; IV_LEN];
+    let mut pt = vec![0x5A; 3 * FILE_CHUNK + 5];
+    pad(&mut pt);
+    let whole = soft::sm4(&key, true, BlockMode::CBC, &iv0, &pt);
+    for enc in [true, false] {
+      let input = if enc { &pt } else { &whole };
+      let mut iv = iv0;
+      let mut out = Vec::new();
+      for chunk in input.chunks(FILE_CHUNK) {
+        let got = soft::sm4(&key, enc, BlockMode::CBC, &iv, chunk);
+        iv = next_iv(enc, chunk, &got);
+        out.extend(got);
+      }
+      assert_eq!(if enc { &whole } else { &pt }, &out);
+    }
+  }
+}
@@ -0,0 +1,78 @@
+use crate::capture::{Dir, Port, Rec};
+use crate::event::{Evt, Sink};
+use crate::protocol::{BlockMode, FrmDec, OpFlag};
+use crate::{Dbg, FRM_HEAD_LEN, FRM_MIN_LEN, FRM_TAIL_LEN, IV_LEN};
+use core::ops::Range;
+use std::sync::Mutex;
+
+#[derive(Clone, Copy, Dbg, PartialEq)]
+#[repr(u8)]
+pub enum Field {
+  Start,
+  Len,
+  Op,
+  Mode,
+  Iv,
+  Payload,
+  Tail,
+}
+
+impl Field {
+  pub const fn name(self) -> &'static str {
+    match self {
+      Self::Start => "起始",
+      Self::Len => "长度",
+      Self::Op => "操作",
+      Self::Mode => "模式",
+      Self::Iv => "IV",
+      Self::Payload => "负载",
+      Self::Tail => "校验",
+    }
+  }
+}
+
+pub fn fields(dir: Dir, frm: &[u8]) -> Vec<(Field, Range<usize>)> {
+  let len = frm.len();
+  if len < FRM_MIN_LEN {
+    return vec![(Field::Payload, 0..len)];
+  }
+  let mut fields =
+    vec![(Field::Start, 0..1), (Field::Len, 1..3), (Field::Op, 3..4), (Field::Mode, 4..5)];
+  let mut payload = FRM_HEAD_LEN..len - FRM_TAIL_LEN;
+  let cbc_req = Dir::Tx == dir
+    && matches!(OpFlag::try_from(frm[3]), Ok(OpFlag::Sm4Enc | OpFlag::Sm4Dec))
+    && Ok(BlockMode::CBC) == BlockMode::try_from(frm[4]);
+  if cbc_req && payload.len() >= IV_LEN {
+    fields.push((Field::Iv, payload.start..payload.start + IV_LEN));
+    payload.start += IV_LEN;
+  }
+  if !payload.is_empty() {
+    fields.push((Field::Payload, payload));
+  }
+  fields.push((Field::Tail, len - FRM_TAIL_LEN..len));
+  fields
+}
+
+pub struct FrmTap<F> {
+  decs: Mutex<(FrmDec, FrmDec)>,
+  on_frm: F,
+}
+
+impl<F: Fn(Rec) + Send + Sync> FrmTap<F> {
+  pub fn new(on_frm: F) -> Self {
+    Self { decs: Mutex::new((FrmDec::default(), FrmDec::default())), on_frm }
+  }
+}
+
+impl<F: Fn(Rec) + Send + Sync> Sink for FrmTap<F> {
+  fn emit(&self, evt: &Evt) {
+    let mut decs = self.decs.lock().unwrap();
+    let (dir, dec, data) = match evt {
+      Evt::Tx(data) => (Dir::Tx, &mut decs.0, data),
+      Evt::Rx(data) => (Dir::Rx, &mut decs.1, data),
+      _ => return,
+    };
+    dec.push(data);
+    dec.flatten().for_each(|data| (self.on_frm)(Rec::now(Port::Dev, dir, data)));
+  }
+}
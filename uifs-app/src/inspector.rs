@@ -0,0 +1,105 @@
+use crate::display::op_name;
+use crate::{AppWindow, FieldSeg, Inspector};
+use core::cell::RefCell;
+use slint::{ComponentHandle, ModelRc, StandardListViewItem, VecModel};
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::time::SystemTime;
+use uifs_app::capture::{Dir, Rec};
+use uifs_app::inspect::fields;
+use uifs_app::protocol::{BlockMode, OpFlag};
+use uifs_app::{slint_f, SlintStr, FRM_HEAD_LEN, FRM_MIN_LEN, FRM_TAIL_LEN};
+
+const INSPECT_MAX_FRMS: usize = 1000;
+
+thread_local! {
+  static FRMS: RefCell<VecDeque<Rec>> = const { RefCell::new(VecDeque::new()) };
+  static ROWS: Rc<VecModel<ModelRc<StandardListViewItem>>> = Rc::new(VecModel::default());
+  static SEGS: Rc<VecModel<FieldSeg>> = Rc::new(VecModel::default());
+  static START: SystemTime = SystemTime::now();
+}
+
+fn spaced_hex(data: &[u8]) -> SlintStr {
+  let hex: Vec<_> = data.iter().map(|b| format!("{b:02x}")).collect();
+  hex.join(" ").into()
+}
+
+fn row(rec: &Rec) -> ModelRc<StandardListViewItem> {
+  let frm = &rec.data;
+  let at = START.with(|start| rec.at.duration_since(*start).unwrap_or_default());
+  let op = frm.get(3).map(|&op| match OpFlag::try_from(op) {
+    Ok(op) => slint_f!("{}", op_name(op)),
+    Err(_) => slint_f!("未知（{op}）"),
+  });
+  let mode = match (frm.get(3).map(|&op| OpFlag::try_from(op)), frm.get(4)) {
+    (Some(Ok(OpFlag::Sm4Enc | OpFlag::Sm4Dec)), Some(&mode)) => match BlockMode::try_from(mode) {
+      Ok(mode) => slint_f!("{mode:?}"),
+      Err(_) => slint_f!("未知（{mode}）"),
+    },
+    _ => slint_f!("-"),
+  };
+  let payload = if frm.len() >= FRM_MIN_LEN {
+    const_hex::encode(&frm[FRM_HEAD_LEN..frm.len() - FRM_TAIL_LEN])
+  } else {
+    String::new()
+  };
+  let cells = [
+    slint_f!("{:.3}", at.as_secs_f64()),
+    slint_f!("{}", if Dir::Tx == rec.dir { "TX" } else { "RX" }),
+    op.unwrap_or_default(),
+    mode,
+    slint_f!("{}", frm.len()),
+    payload.into(),
+    const_hex::encode(frm).into(),
+  ];
+  let cells: Vec<_> = cells.into_iter().map(StandardListViewItem::from).collect();
+  ModelRc::from(cells.as_slice())
+}
+
+pub fn push(rec: Rec) {
+  let row = row(&rec);
+  FRMS.with_borrow_mut(|frms| {
+    frms.push_back(rec);
+    if frms.len() > INSPECT_MAX_FRMS {
+      frms.pop_front();
+      ROWS.with(|rows| rows.remove(0));
+    }
+  });
+  ROWS.with(|rows| rows.push(row));
+}
+
+pub fn frames() -> Vec<(SystemTime, Dir, u8)> {
+  FRMS.with_borrow(|frms| {
+    frms.iter().filter(|rec| rec.data.len() > 3).map(|rec| (rec.at, rec.dir, rec.data[3])).collect()
+  })
+}
+
+fn select(idx: i32) {
+  let segs: Vec<_> = FRMS.with_borrow(|frms| {
+    let Some(rec) = frms.get(idx as usize) else { return Vec::new() };
+    fields(rec.dir, &rec.data)
+      .into_iter()
+      .map(|(field, range)| FieldSeg {
+        name: field.name().into(),
+        hex: spaced_hex(&rec.data[range]),
+        kind: field as i32,
+      })
+      .collect()
+  });
+  SEGS.with(|model| model.set_vec(segs));
+}
+
+fn clear() {
+  FRMS.with_borrow_mut(VecDeque::clear);
+  ROWS.with(|rows| rows.set_vec(Vec::new()));
+  SEGS.with(|segs| segs.set_vec(Vec::new()));
+}
+
+pub fn init(app: &AppWindow) {
+  START.with(|_| ());
+  let inspector = app.global::<Inspector>();
+  ROWS.with(|rows| inspector.set_rows(ModelRc::from(rows.clone())));
+  SEGS.with(|segs| inspector.set_segs(ModelRc::from(segs.clone())));
+  inspector.on_select(select);
+  inspector.on_clear(clear);
+}
@@ -0,0 +1,167 @@
+use crate::{blk_text, field, status, AppWindow, Field, Options, WEAK_APP};
+use core::cell::RefCell;
+use slint::{ComponentHandle, ModelRc};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+use uifs_app::conf::conf_dir;
+use uifs_app::keystore::{KeyStore, Sealed};
+use uifs_app::{check, slint_f, we, Opt, Rst, SlintStr};
+
+thread_local! {
+  static STORE: RefCell<Opt<KeyStore>> = const { RefCell::new(None) };
+}
+
+fn store_path() -> Rst<PathBuf> {
+  let Some(dir) = conf_dir() else {
+    we!("无法确定配置目录");
+  };
+  Ok(dir.join(KeyStore::FILE))
+}
+
+fn persist(store: &KeyStore) -> Rst<()> {
+  store.seal()?.save(&store_path()?)
+}
+
+fn refresh() {
+  let app = WEAK_APP.with(|w| w.get().unwrap().unwrap());
+  let opts = app.global::<Options>();
+  let names: Vec<SlintStr> =
+    STORE.with_borrow(|store| store.iter().flat_map(KeyStore::names).map(Into::into).collect());
+  opts.set_ks_unlocked(STORE.with_borrow(Opt::is_some));
+  opts.set_ks_exists(store_path().is_ok_and(|path| path.exists()));
+  opts.set_ks_names(ModelRc::from(names.as_slice()));
+}
+
+fn report(rst: Rst<SlintStr>) {
+  match rst {
+    Ok(line) => {
+      info!("{line}");
+      status(line, false);
+    }
+    Err(e) => {
+      warn!("{e}");
+      status(slint_f!("{e}"), true);
+    }
+  }
+  refresh();
+}
+
+fn edit(f: impl FnOnce(&mut KeyStore) -> Rst<SlintStr>) -> Rst<SlintStr> {
+  STORE.with_borrow_mut(|store| {
+    let Some(store) = store.as_mut() else {
+      we!("密钥库未解锁");
+    };
+    let line = f(store)?;
+    persist(store)?;
+    Ok(line)
+  })
+}
+
+fn unlock(pass: &str) -> Rst<SlintStr> {
+  let Some(sealed) = Sealed::load(&store_path()?)? else {
+    we!("密钥库不存在，请输入并确认口令后新建");
+  };
+  let store = KeyStore::unlock(&sealed, pass)?;
+  let n = store.names().count();
+  STORE.set(Some(store));
+  Ok(slint_f!("密钥库已解锁，共 {n} 个密钥"))
+}
+
+fn create(pass: &str, confirm: &str) -> Rst<SlintStr> {
+  if pass != confirm {
+    we!("两次输入的口令不一致");
+  }
+  if Sealed::load(&store_path()?)?.is_some() {
+    we!("密钥库已存在，请直接解锁");
+  }
+  let store = KeyStore::create(pass)?;
+  persist(&store)?;
+  STORE.set(Some(store));
+  Ok(slint_f!("已新建密钥库"))
+}
+
+fn import(path: &str, pass: &str) -> Rst<SlintStr> {
+  let Some(sealed) = Sealed::load(Path::new(path))? else {
+    we!("文件不存在：{path}");
+  };
+  let other = KeyStore::unlock(&sealed, pass)?;
+  edit(|store| {
+    let n = store.import(other)?;
+    Ok(slint_f!("已导入 {n} 个密钥"))
+  })
+}
+
+fn export(path: &str, pass: &str) -> Rst<SlintStr> {
+  STORE.with_borrow(|store| {
+    let Some(store) = store.as_ref() else {
+      we!("密钥库未解锁");
+    };
+    store.export(pass)?.save(Path::new(path))?;
+    Ok(slint_f!("已导出 {} 个密钥到 {path}", store.names().count()))
+  })
+}
+
+fn restore_key() {
+  let app = WEAK_APP.with(|w| w.get().unwrap().unwrap());
+  let opts = app.global::<Options>();
+  let name = opts.get_ks_key();
+  if name.is_empty() {
+    return;
+  }
+  let Some(key) = STORE.with_borrow(|store| store.as_ref().and_then(|store| store.get(&name)))
+  else {
+    return;
+  };
+  opts.set_key_text(blk_text(Field::Key, &key));
+  opts.set_key_ready(false);
+  opts.set_key_err(SlintStr::new());
+  info!("已载入上次使用的密钥“{name}”");
+  status(slint_f!("已载入上次使用的密钥“{name}”，请注入密钥"), false);
+}
+
+pub fn init(app: &AppWindow) {
+  let opts = app.global::<Options>();
+  refresh();
+  opts.on_ks_unlock(|pass| {
+    report(unlock(&pass));
+    restore_key();
+  });
+  opts.on_ks_create(|pass, confirm| report(create(&pass, &confirm)));
+  opts.on_ks_lock(|| {
+    STORE.set(None);
+    report(Ok(slint_f!("密钥库已锁定")));
+  });
+  opts.on_ks_pick(|name| {
+    let Some(key) = STORE.with_borrow(|store| store.as_ref().and_then(|store| store.get(&name)))
+    else {
+      warn!("密钥“{name}”不存在");
+      status(slint_f!("密钥“{name}”不存在"), true);
+      return SlintStr::new();
+    };
+    status(slint_f!("已载入密钥“{name}”"), false);
+    WEAK_APP.with(|w| w.get().unwrap().unwrap()).global::<Options>().set_ks_key(name);
+    blk_text(Field::Key, &key)
+  });
+  opts.on_ks_save(|name, k| {
+    let Some(key) = field(Field::Key, &k, "密钥", check::blk) else { return };
+    report(edit(|store| {
+      store.put(&name, key)?;
+      Ok(slint_f!("已保存密钥“{}”", name.trim()))
+    }));
+    if STORE.with_borrow(|store| store.as_ref().and_then(|store| store.get(name.trim()))).is_some()
+    {
+      let app = WEAK_APP.with(|w| w.get().unwrap().unwrap());
+      app.global::<Options>().set_ks_key(name.trim().into());
+    }
+  });
+  opts.on_ks_remove(|name| {
+    report(edit(|store| {
+      if !store.remove(&name) {
+        we!("密钥“{name}”不存在");
+      }
+      Ok(slint_f!("已删除密钥“{name}”"))
+    }));
+  });
+  opts.on_ks_import(|path, pass| report(import(&path, &pass)));
+  opts.on_ks_export(|path, pass| report(export(&path, &pass)));
+}
@@ -0,0 +1,228 @@
+use crate::soft::random;
+use crate::{mk_err_str, we, Dbg, Opt, Rst, Str, KEY_LEN};
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::ChaCha20Poly1305;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const VERSION: u32 = 1;
+
+#[derive(Clone, Dbg, PartialEq, Serialize, Deserialize)]
+pub struct Sealed {
+  pub version: u32,
+  pub salt: Str,
+  pub nonce: Str,
+  pub data: Str,
+}
+
+impl Sealed {
+  pub fn load(path: &Path) -> Rst<Opt<Self>> {
+    let text = match std::fs::read_to_string(path) {
+      Ok(text) => text,
+      Err(e) if std::io::ErrorKind::NotFound == e.kind() => return Ok(None),
+      Err(e) => {
+        we!("{}", mk_err_str(e, "密钥库读取失败"));
+      }
+    };
+    match toml::from_str(&text) {
+      Ok(sealed) => Ok(Some(sealed)),
+      Err(e) => {
+        we!("{}", mk_err_str(e, "密钥库解析失败"));
+      }
+    }
+  }
+
+  pub fn save(&self, path: &Path) -> Rst<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+      if let Err(e) = std::fs::create_dir_all(dir) {
+        we!("{}", mk_err_str(e, "密钥库目录创建失败"));
+      }
+    }
+    let text = match toml::to_string_pretty(self) {
+      Ok(text) => text,
+      Err(e) => {
+        we!("{}", mk_err_str(e, "密钥库序列化失败"));
+      }
+    };
+    if let Err(e) = std::fs::write(path, text) {
+      we!("{}", mk_err_str(e, "密钥库写入失败"));
+    }
+    Ok(())
+  }
+}
+
+fn derive(pass: &str, salt: &[u8; SALT_LEN]) -> Rst<[u8; 32]> {
+  let mut key = [0; 32];
+  if let Err(e) = Argon2::default().hash_password_into(pass.as_bytes(), salt, &mut key) {
+    we!("口令派生失败：{e}");
+  }
+  Ok(key)
+}
+
+fn unhex<const N: usize>(s: &str, what: &str) -> Rst<[u8; N]> {
+  match const_hex::decode_to_array(s) {
+    Ok(data) => Ok(data),
+    Err(e) => {
+      we!("密钥库{what}无效：{e}");
+    }
+  }
+}
+
+#[derive(Clone, Default)]
+pub struct KeyStore {
+  salt: [u8; SALT_LEN],
+  cipher: [u8; 32],
+  keys: BTreeMap<Str, [u8; KEY_LEN]>,
+}
+
+impl KeyStore {
+  pub const FILE: &'static str = "keys.toml";
+
+  pub fn create(pass: &str) -> Rst<Self> {
+    if pass.is_empty() {
+      we!("口令不能为空");
+    }
+    let salt = random()?;
+    Ok(Self { salt, cipher: derive(pass, &salt)?, keys: BTreeMap::new() })
+  }
+
+  pub fn unlock(sealed: &Sealed, pass: &str) -> Rst<Self> {
+    if VERSION != sealed.version {
+      we!("不支持的密钥库版本：{}", sealed.version);
+    }
+    let salt = unhex(&sealed.salt, "盐值")?;
+    let nonce: [u8; NONCE_LEN] = unhex(&sealed.nonce, "随机数")?;
+    let data = match const_hex::decode(&sealed.data) {
+      Ok(data) => data,
+      Err(e) => {
+        we!("密钥库数据无效：{e}");
+      }
+    };
+    let cipher = derive(pass, &salt)?;
+    let Ok(plain) = ChaCha20Poly1305::new(&cipher.into()).decrypt(&nonce.into(), &data[..]) else {
+      we!("口令错误或密钥库已损坏");
+    };
+    let Ok(entries) = toml::from_str::<BTreeMap<Str, Str>>(&String::from_utf8_lossy(&plain)) else {
+      we!("密钥库内容无效");
+    };
+    let mut keys = BTreeMap::new();
+    for (name, key) in entries {
+      keys.insert(name, unhex(&key, "密钥")?);
+    }
+    Ok(Self { salt, cipher, keys })
+  }
+
+  pub fn seal(&self) -> Rst<Sealed> {
+    let entries: BTreeMap<_, _> =
+      self.keys.iter().map(|(name, key)| (name, const_hex::encode(key))).collect();
+    let plain = match toml::to_string(&entries) {
+      Ok(plain) => plain,
+      Err(e) => {
+        we!("{}", mk_err_str(e, "密钥库序列化失败"));
+      }
+    };
+    let nonce: [u8; NONCE_LEN] = random()?;
+    let cipher = ChaCha20Poly1305::new(&self.cipher.into());
+    let Ok(data) = cipher.encrypt(&nonce.into(), plain.as_bytes()) else {
+      we!("密钥库加密失败");
+    };
+    Ok(Sealed {
+      version: VERSION,
+      salt: const_hex::encode(self.salt),
+      nonce: const_hex::encode(nonce),
+      data: const_hex::encode(data),
+    })
+  }
+
+  pub fn export(&self, pass: &str) -> Rst<Sealed> {
+    Self { keys: self.keys.clone(), ..Self::create(pass)? }.seal()
+  }
+
+  pub fn import(&mut self, other: Self) -> Rst<usize> {
+    let dups: Vec<_> = other.names().filter(|name| self.keys.contains_key(*name)).collect();
+    if !dups.is_empty() {
+      we!("密钥名称已存在：{}，请先删除或改名后再导入", dups.join("、"));
+    }
+    let n = other.keys.len();
+    self.keys.extend(other.keys);
+    Ok(n)
+  }
+
+  pub fn names(&self) -> impl Iterator<Item = &str> {
+    self.keys.keys().map(Str::as_str)
+  }
+
+  pub fn get(&self, name: &str) -> Opt<[u8; KEY_LEN]> {
+    self.keys.get(name).copied()
+  }
+
+  pub fn put(&mut self, name: &str, key: [u8; KEY_LEN]) -> Rst<()> {
+    let name = name.trim();
+    if name.is_empty() {
+      we!("密钥名称不能为空");
+    }
+    self.keys.insert(name.into(), key);
+    Ok(())
+  }
+
+  pub fn remove(&mut self, name: &str) -> bool {
+    self.keys.remove(name).is_some()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn seal_unseal() {
+    let mut store = KeyStore::create("pass").unwrap();
+    store.put(" lab ", [1; KEY_LEN]).unwrap();
+    store.put("demo", [2; KEY_LEN]).unwrap();
+    let sealed = store.seal().unwrap();
+    let text = toml::to_string(&sealed).unwrap();
+    let sealed: Sealed = toml::from_str(&text).unwrap();
+    let store = KeyStore::unlock(&sealed, "pass").unwrap();
+    assert_eq!(vec!["demo", "lab"], store.names().collect::<Vec<_>>());
+    assert_eq!(Some([1; KEY_LEN]), store.get("lab"));
+    assert_eq!(Some([2; KEY_LEN]), store.get("demo"));
+  }
+
+  #[test]
+  fn wrong_pass() {
+    let sealed = KeyStore::create("pass").unwrap().seal().unwrap();
+    assert!(KeyStore::unlock(&sealed, "Pass").is_err());
+    assert!(KeyStore::create("").is_err());
+  }
+
+  #[test]
+  fn tampered() {
+    let mut sealed = KeyStore::create("pass").unwrap().seal().unwrap();
+    sealed.data.replace_range(0..2, if sealed.data.starts_with("00") { "01" } else { "00" });
+    assert!(KeyStore::unlock(&sealed, "pass").is_err());
+    sealed.version = VERSION + 1;
+    assert!(KeyStore::unlock(&sealed, "pass").is_err());
+  }
+
+  #[test]
+  fn export_import() {
+    let mut store = KeyStore::create("pass").unwrap();
+    store.put("lab", [1; KEY_LEN]).unwrap();
+    let exported = store.export("other").unwrap();
+    assert!(KeyStore::unlock(&exported, "pass").is_err());
+    let other = KeyStore::unlock(&exported, "other").unwrap();
+    let mut mine = KeyStore::create("mine").unwrap();
+    assert_eq!(1, mine.import(other.clone()).unwrap());
+    assert_eq!(Some([1; KEY_LEN]), mine.get("lab"));
+    mine.put("lab", [3; KEY_LEN]).unwrap();
+    assert!(mine.import(other).is_err());
+    assert_eq!(Some([3; KEY_LEN]), mine.get("lab"));
+    assert!(mine.put("  ", [0; KEY_LEN]).is_err());
+    assert!(mine.remove("lab"));
+    assert!(!mine.remove("lab"));
+  }
+}
@@ -0,0 +1,129 @@
+use crate::event::{Evt, Sink};
+use crate::protocol::OpFlag;
+use crate::{Dbg, Opt};
+use core::time::Duration as Durn;
+use std::sync::Mutex;
+
+pub const HIST_BINS: usize = 20;
+
+#[derive(Clone, Copy, Dbg, PartialEq)]
+pub struct Summary {
+  pub n: usize,
+  pub min: Durn,
+  pub median: Durn,
+  pub p99: Durn,
+  pub max: Durn,
+}
+
+#[derive(Default)]
+pub struct Latency {
+  samples: Mutex<[Vec<Durn>; OpFlag::ALL.len()]>,
+}
+
+fn idx(op: OpFlag) -> usize {
+  OpFlag::ALL.iter().position(|&o| op == o).unwrap()
+}
+
+fn rank(sorted: &[Durn], q: f64) -> Durn {
+  let i = ((sorted.len() as f64 * q).ceil() as usize).clamp(1, sorted.len());
+  sorted[i - 1]
+}
+
+impl Latency {
+  pub const fn new() -> Self {
+    Self { samples: Mutex::new([Vec::new(), Vec::new(), Vec::new(), Vec::new()]) }
+  }
+
+  pub fn record(&self, op: OpFlag, rtt: Durn) {
+    self.samples.lock().unwrap()[idx(op)].push(rtt);
+  }
+
+  pub fn reset(&self) {
+    self.samples.lock().unwrap().iter_mut().for_each(Vec::clear);
+  }
+
+  pub fn sorted(&self, op: OpFlag) -> Vec<Durn> {
+    let mut sorted = self.samples.lock().unwrap()[idx(op)].clone();
+    sorted.sort_unstable();
+    sorted
+  }
+
+  pub fn summary(sorted: &[Durn]) -> Opt<Summary> {
+    Some(Summary {
+      n: sorted.len(),
+      min: *sorted.first()?,
+      median: rank(sorted, 0.5),
+      p99: rank(sorted, 0.99),
+      max: *sorted.last()?,
+    })
+  }
+
+  pub fn hist(sorted: &[Durn]) -> [u32; HIST_BINS] {
+    let mut bins = [0; HIST_BINS];
+    let (Some(min), Some(max)) = (sorted.first(), sorted.last()) else { return bins };
+    let span = (*max - *min).as_secs_f64();
+    for rtt in sorted {
+      let pos = if 0.0 == span { 0.0 } else { (*rtt - *min).as_secs_f64() / span };
+      bins[((pos * HIST_BINS as f64) as usize).min(HIST_BINS - 1)] += 1;
+    }
+    bins
+  }
+}
+
+impl Sink for Latency {
+  fn emit(&self, evt: &Evt) {
+    if let Evt::Rtt { op, rtt } = evt {
+      self.record(*op, *rtt);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn ms(ms: u64) -> Durn {
+    Durn::from_millis(ms)
+  }
+
+  #[test]
+  fn percentiles() {
+    assert_eq!(None, Latency::summary(&[]));
+    let one = Latency::summary(&[ms(7)]).unwrap();
+    assert_eq!((1, ms(7), ms(7), ms(7), ms(7)), (one.n, one.min, one.median, one.p99, one.max));
+    let sorted: Vec<_> = (1..=100).map(ms).collect();
+    let sum = Latency::summary(&sorted).unwrap();
+    assert_eq!(
+      (100, ms(1), ms(50), ms(99), ms(100)),
+      (sum.n, sum.min, sum.median, sum.p99, sum.max)
+    );
+    let sorted: Vec<_> = (1..=5).map(ms).collect();
+    let sum = Latency::summary(&sorted).unwrap();
+    assert_eq!((ms(3), ms(5)), (sum.median, sum.p99));
+  }
+
+  #[test]
+  fn per_op_samples() {
+    let lat = Latency::new();
+    lat.emit(&Evt::Rtt { op: OpFlag::Sm3, rtt: ms(3) });
+    lat.emit(&Evt::Rtt { op: OpFlag::Sm3, rtt: ms(1) });
+    lat.record(OpFlag::Key, ms(2));
+    assert_eq!(vec![ms(1), ms(3)], lat.sorted(OpFlag::Sm3));
+    assert_eq!(vec![ms(2)], lat.sorted(OpFlag::Key));
+    assert!(lat.sorted(OpFlag::Sm4Enc).is_empty());
+    lat.reset();
+    assert!(lat.sorted(OpFlag::Sm3).is_empty());
+  }
+
+  #[test]
+  fn hist_bins() {
+    assert_eq!([0; HIST_BINS], Latency::hist(&[]));
+    let mut same = [0; HIST_BINS];
+    same[0] = 3;
+    assert_eq!(same, Latency::hist(&[ms(4); 3]));
+    let sorted: Vec<_> = (0..HIST_BINS as u64).map(ms).collect();
+    let bins = Latency::hist(&sorted);
+    assert_eq!(sorted.len() as u32, bins.iter().sum::<u32>());
+    assert_eq!(1, bins[HIST_BINS - 1]);
+  }
+}
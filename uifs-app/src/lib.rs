@@ -7,8 +7,31 @@ pub use snafu::whatever as we;
 pub use std::format as f;
 pub use std::string::String as Str;
 
+pub mod bench;
+pub mod capture;
+pub mod check;
+pub mod codec;
+pub mod conf;
+pub mod echo;
+pub mod event;
+pub mod file;
+pub mod inspect;
+pub mod keystore;
+pub mod latency;
+pub mod obsr;
+pub mod pcap;
+pub mod pending;
+pub mod probe;
+pub mod protocol;
+pub mod replay;
+pub mod soft;
+pub mod stats;
+pub mod term;
+pub mod vcd;
+
 pub type Rst<T, E = snafu::Whatever> = Result<T, E>;
 
+pub const BLK_LEN: usize = 16;
 pub const FRM_HEAD_LEN: usize = 1 + 2 + 1 + 1;
 pub const FRM_MIN_LEN: usize = FRM_HEAD_LEN + FRM_TAIL_LEN;
 pub const FRM_MAX_LEN: usize = FRM_HEAD_LEN + 65408 + FRM_TAIL_LEN;
@@ -22,6 +45,7 @@ pub const RX_SM3_RTN_LEN: usize = FRM_HEAD_LEN + 32;
 pub const SM3_HASH_LEN: usize = 32;
 pub const SM3_PAD_FLAG: u8 = 0x80;
 pub const SP_BAUD_RATE: u32 = 115_200;
+pub const SP_SCAN_ITV: Durn = Durn::from_secs(2);
 pub const SP_TIMEOUT: Durn = Durn::from_secs(1);
 pub const TX_MSG_MAX_LEN: usize = 65408;
 
@@ -29,7 +29,7 @@ impl<'s> Config<'s> {
     let dir = match tokio::fs::canonicalize(self.log_dir).await {
       Ok(f) => f,
       Err(e) => {
-        we!("{} conf={self:?}", mk_err_str(e, "Failed to canonicalize log directory!"))
+        we!("{} conf={self:?}", mk_err_str(e, "Failed to canonicalize log directory!"));
       }
     };
     let mut layers = Vec::new();
@@ -1,44 +1,90 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 slint::include_modules!();
 
+mod chat;
+mod display;
+mod inspector;
+mod keys;
 mod logger;
-mod protocol;
+mod plot;
 mod receiver;
+mod settings;
+mod terminal;
 
-use serialport::{SerialPort, SerialPortInfo, SerialPortType};
+use serialport::{
+  DataBits, FlowControl, Parity, SerialPort, SerialPortInfo, SerialPortType, StopBits,
+};
 use slint::{invoke_from_event_loop, ModelRc, Weak};
 
 use tracing::{debug, error, info, trace, warn};
-use uifs_app::{mk_err_str, slint_f, we, Opt, Rst, SP_BAUD_RATE, SP_TIMEOUT, TX_MSG_MAX_LEN};
+use uifs_app::bench::{self, BenchConf, BenchOp, BenchRst};
+use uifs_app::capture::{self, Capture, Port};
+use uifs_app::check;
+use uifs_app::codec::Codec;
+use uifs_app::conf::{self, AppConf, SpConf};
+use uifs_app::echo::{LineEnd, Loopback};
+use uifs_app::event::{Evt, FnSink, Hub, LogSink, Sink};
+use uifs_app::file::{self, FileJob, FileOp, FileRst};
+use uifs_app::inspect::FrmTap;
+use uifs_app::latency::{self, Latency as LatencyStats};
+use uifs_app::obsr::Obsr;
+use uifs_app::pcap;
+use uifs_app::pending::{Act, Pending};
+use uifs_app::probe;
+use uifs_app::replay;
+use uifs_app::soft;
+use uifs_app::stats::{Counts, Stats};
+use uifs_app::{mk_err_str, slint_f, we, Opt, Rst, SlintStr, Str, SP_BAUD_RATE, SP_SCAN_ITV};
 
-use crate::protocol::{key, sm3, sm4_dec_cbc, sm4_dec_ecb, sm4_enc_cbc, sm4_enc_ecb};
+use uifs_app::protocol::{
+  key, sm3, sm4_dec_cbc, sm4_dec_ecb, sm4_enc_cbc, sm4_enc_ecb, BlockMode, OpFlag,
+};
+
+use bytes::Bytes;
+use core::time::Duration as Durn;
+use std::time::Instant;
 
 use core::cell::{OnceCell, RefCell};
 thread_local! {
-  static ALL_SPS: OnceCell<Vec<SerialPortInfo>> = OnceCell::new();
-  static CUR_LSN_HNDLR: RefCell<Opt<tokio::task::JoinHandle<()>>> = RefCell::new(None);
-  static CUR_SP: RefCell<Opt<Box<dyn SerialPort>>> = RefCell::new(None);
-  static WEAK_APP: OnceCell<Weak<AppWindow>> = OnceCell::new();
+  static LAST_BENCH: RefCell<Opt<BenchRst>> = const { RefCell::new(None) };
+  static ALL_SPS: RefCell<Vec<(SerialPortInfo, bool)>> = const { RefCell::new(Vec::new()) };
+  static CUR_LSN_HNDLR: RefCell<Opt<tokio::task::JoinHandle<()>>> = const { RefCell::new(None) };
+  static CUR_SP: RefCell<Opt<Box<dyn SerialPort>>> = const { RefCell::new(None) };
+  static CUR_SP_NAME: RefCell<Opt<Str>> = const { RefCell::new(None) };
+  static LOST_SP: RefCell<Opt<SerialPortInfo>> = const { RefCell::new(None) };
+  static WEAK_APP: OnceCell<Weak<AppWindow>> = const { OnceCell::new() };
 }
 
-use core::sync::atomic::AtomicI32;
 use core::sync::atomic::Ordering::Relaxed;
-use std::sync::atomic::AtomicU8;
-static CUR_SP_IDX: AtomicI32 = AtomicI32::new(-1);
+use std::sync::atomic::{AtomicBool, AtomicU8};
 static CUR_MODE: AtomicU8 = AtomicU8::new(0);
+static RAW: AtomicBool = AtomicBool::new(false);
+static FILE_CANCEL: AtomicBool = AtomicBool::new(false);
+static CAPTURE: Capture = Capture::new();
+static HUB: Hub = Hub::new();
+static PENDING: Pending = Pending::new();
+static LATENCY: LatencyStats = LatencyStats::new();
+static LOOPBACK: Loopback = Loopback::new();
+static STATS: Stats = Stats::new();
 
 #[tokio::main(worker_threads = 1)]
 async fn main() -> Rst<()> {
   // !!! the result should never be ignored or named `_` !!!
+  let (app_conf, conf_err) = match conf::try_load::<AppConf>(AppConf::FILE) {
+    Ok(app_conf) => (app_conf, None),
+    Err(e) => (AppConf::default(), Some(e.to_string())),
+  };
   let _guards = {
-    use std::env::var;
-    let log_dir = var("UIFS_LOG_DIR").unwrap_or("./log".to_string());
-    let log_to_file = var("UIFS_DIS_LOG_FILE").is_err();
-    let log_to_cnsl = var("UIFS_ENBL_LOG_CNSL").is_ok();
-    logger::Config::new(&log_dir, log_to_file, log_to_cnsl).init().await?
+    let log_conf = app_conf.with_env();
+    logger::Config::new(&log_conf.log_dir, log_conf.log_to_file, log_conf.log_to_cnsl)
+      .init()
+      .await?
   };
 
   trace!("Tracing Initialization finished.");
+  if let Some(e) = &conf_err {
+    warn!("{e}");
+  }
   #[cfg(debug_assertions)]
   debug!("Running on debug mode.");
 
@@ -54,328 +100,676 @@ async fn main() -> Rst<()> {
   WEAK_APP.with(|weak| {
     let _ = weak.set(app.as_weak());
   });
+  HUB.subscribe(std::sync::Arc::new(LogSink));
+  HUB.subscribe(std::sync::Arc::new(FnSink(|evt: &Evt| {
+    let Evt::Rsp(rsp) = evt else { return };
+    if let Some(rtt) = PENDING.complete(rsp.op(), Instant::now()) {
+      HUB.emit(&Evt::Rtt { op: rsp.op(), rtt });
+    }
+  })));
+  HUB.subscribe(std::sync::Arc::new(display::AppSink::new(app.as_weak())));
+  HUB.subscribe(std::sync::Arc::new(FnSink(|evt: &Evt| {
+    let Evt::Echo(data) = evt else { return };
+    let check = LOOPBACK.check(data.clone());
+    invoke_from_event_loop(move || {
+      let app = WEAK_APP.with(|w| w.get().unwrap().unwrap());
+      let opts = app.global::<Options>();
+      if !opts.get_loopback() {
+        return;
+      }
+      match check.first_mismatch() {
+        None => opts.invoke_append_dp_text(slint_f!("自检：{} 字节一致", check.got.len())),
+        Some(off) => opts.invoke_append_dp_text(slint_f!(
+          "自检：{} 字节不一致（首个偏移 {off}）：{}",
+          check.mismatches(),
+          check.marked()
+        )),
+      }
+    })
+    .unwrap();
+  })));
+  HUB.subscribe(std::sync::Arc::new(chat::ChatSink::new(app.as_weak(), false)));
+  HUB.subscribe(std::sync::Arc::new(&LATENCY));
+  HUB.subscribe(std::sync::Arc::new(CAPTURE.tap(Port::Dev)));
+  HUB.subscribe(std::sync::Arc::new(&STATS));
+  HUB.subscribe(std::sync::Arc::new(FrmTap::new(|rec| {
+    invoke_from_event_loop(move || inspector::push(rec)).unwrap();
+  })));
+  inspector::init(&app);
+  HUB.subscribe(std::sync::Arc::new(FnSink(|evt: &Evt| {
+    let Evt::Obsr(data) = evt else { return };
+    if let Some(obsr) = Obsr::parse(data) {
+      invoke_from_event_loop(move || plot::push(obsr)).unwrap();
+    }
+  })));
+  plot::init(&app);
+  HUB.subscribe(std::sync::Arc::new(FnSink(|evt: &Evt| {
+    let Evt::Rx(data) = evt else { return };
+    let data = data.clone();
+    invoke_from_event_loop(move || terminal::feed(&data)).unwrap();
+  })));
+  terminal::init(&app);
+  HUB.subscribe(std::sync::Arc::new(FnSink(|evt: &Evt| {
+    if let Evt::Disconnected(e) = evt {
+      let line = slint_f!("串口连接中断：{e}");
+      invoke_from_event_loop(move || {
+        close_sp(true);
+        status(line, true);
+      })
+      .unwrap();
+    }
+  })));
+  chat::PEER_HUB.subscribe(std::sync::Arc::new(LogSink));
+  chat::PEER_HUB.subscribe(std::sync::Arc::new(CAPTURE.tap(Port::Peer)));
+  chat::PEER_HUB.subscribe(std::sync::Arc::new(&STATS));
+  chat::PEER_HUB.subscribe(std::sync::Arc::new(chat::ChatSink::new(app.as_weak(), true)));
+  chat::PEER_HUB.subscribe(std::sync::Arc::new(FnSink(|evt: &Evt| {
+    if let Evt::Disconnected(_) = evt {
+      invoke_from_event_loop(chat::close_peer).unwrap();
+    }
+  })));
 
-  match serialport::available_ports() {
-    Ok(all_sps) => {
-      debug!(all_sps = ?all_sps, "可用串口查询成功");
-      let slint_sps: Vec<_> = all_sps
-        .iter()
-        .map(|sp| {
-          if let SerialPortType::UsbPort(_) = sp.port_type {
-            slint_f!("{} UsbPort", sp.port_name)
-          } else {
-            slint_f!("{} {:?}", sp.port_name, sp.port_type)
+  tokio::spawn(async {
+    let mut itv = tokio::time::interval(Durn::from_millis(10));
+    loop {
+      itv.tick().await;
+      for act in PENDING.poll(Instant::now()) {
+        match act {
+          Act::Resend(op, frm, tries) => {
+            HUB.emit(&Evt::Resend { op, tries });
+            invoke_from_event_loop(move || {
+              write_sp(&frm, "请求重发失败");
+            })
+            .unwrap();
           }
-        })
-        .collect();
+          Act::Timeout(op, waited) => HUB.emit(&Evt::Timeout { op, waited }),
+        }
+      }
+    }
+  });
 
-      app.global::<Options>().set_sps(ModelRc::from(slint_sps.as_slice()));
-      ALL_SPS.with(|oc| oc.set(all_sps).unwrap());
-      trace!("串口列表设置成功");
+  tokio::spawn(async {
+    let mut itv = tokio::time::interval(Durn::from_secs(1));
+    loop {
+      itv.tick().await;
+      let rate = STATS.throughput(Instant::now());
+      let counts = STATS.snapshot();
+      invoke_from_event_loop(move || {
+        let app = WEAK_APP.with(|w| w.get().unwrap().unwrap());
+        app.global::<Options>().set_stats(stats_to_ui(&counts, rate));
+        app.global::<Latency>().set_ops(ModelRc::from(latency_to_ui().as_slice()));
+      })
+      .unwrap();
     }
+  });
+
+  tokio::spawn(async {
+    let mut itv = tokio::time::interval(Durn::from_millis(100));
+    loop {
+      itv.tick().await;
+      invoke_from_event_loop(plot::render).unwrap();
+    }
+  });
+
+  tokio::spawn(async {
+    let mut itv = tokio::time::interval(Durn::from_millis(30));
+    loop {
+      itv.tick().await;
+      invoke_from_event_loop(terminal::render).unwrap();
+    }
+  });
+
+  sp_conf_to_ui(&app.global::<Options>(), &conf::load(SpConf::FILE));
+
+  match list_sps() {
+    Ok(sps) => apply_sps(sps),
     Err(e) => {
-      let e = mk_err_str(e, "无法获取可用串口");
       error!("{e}");
-      we!("{e}");
+      return Err(e);
     }
-  };
+  }
+
+  settings::init(&app, app_conf, conf_err);
+
+  tokio::spawn(async {
+    let mut itv = tokio::time::interval(SP_SCAN_ITV);
+    loop {
+      itv.tick().await;
+      scan_sps().await;
+    }
+  });
+
+  app.global::<Options>().on_sp_scan(|| {
+    tokio::spawn(scan_sps());
+  });
+
+  app.global::<Options>().on_sp_find(|| {
+    let paths: Vec<_> = ALL_SPS.with_borrow(|sps| {
+      let busy = [CUR_SP_NAME.with_borrow(Clone::clone), chat::peer_name()];
+      sps
+        .iter()
+        .filter(|(sp, gone)| !gone && !busy.contains(&Some(sp.port_name.clone())))
+        .map(|(sp, _)| sp.port_name.clone())
+        .collect()
+    });
+    let weak_app = WEAK_APP.with(|w| w.get().unwrap().clone());
+    let sp_conf = sp_conf_from_ui(&weak_app.unwrap().global::<Options>());
+    weak_app.unwrap().global::<Options>().set_sp_finding(true);
+    info!(paths = ?paths, "开始查找设备");
+    tokio::task::spawn_blocking(move || {
+      let found = probe::find(&paths, &sp_conf);
+      invoke_from_event_loop(move || {
+        let app = weak_app.unwrap();
+        let opts = app.global::<Options>();
+        opts.set_sp_finding(false);
+        let Some((path, baud_rate)) = found else {
+          opts.invoke_append_dp_text(slint_f!("未找到设备"));
+          return;
+        };
+        info!(path = path, baud_rate = baud_rate, "已找到设备");
+        if let Some(idx) =
+          ALL_SPS.with_borrow(|sps| sps.iter().position(|(sp, _)| sp.port_name == path))
+        {
+          opts.set_sp_idx(idx as i32);
+        }
+        opts.set_sp_baud_rate(slint_f!("{baud_rate}"));
+        opts.invoke_append_dp_text(slint_f!("已找到设备：{path}，波特率 {baud_rate}"));
+      })
+      .unwrap();
+    });
+  });
 
   app.global::<Options>().on_sp_open(|sel_sp_idx| {
-    if sel_sp_idx == CUR_SP_IDX.load(Relaxed) {
+    let Some((sel_sp, gone)) = ALL_SPS.with_borrow(|sps| sps.get(sel_sp_idx as usize).cloned())
+    else {
+      return false;
+    };
+    let baud_rate =
+      WEAK_APP.with(|w| w.get().unwrap().unwrap().global::<Options>().get_sp_baud_rate());
+    if !baud_rate.parse::<u32>().is_ok_and(|rate| 0 != rate) {
+      warn!(baud_rate = ?baud_rate, "波特率无效");
+      status(slint_f!("波特率无效：{baud_rate}"), true);
+      return false;
+    }
+    let sp_conf =
+      WEAK_APP.with(|w| sp_conf_from_ui(&w.get().unwrap().unwrap().global::<Options>()));
+    if CUR_SP_NAME.with_borrow(|name| Some(&sel_sp.port_name) == name.as_ref()) {
+      let applied = CUR_SP.with_borrow_mut(|sp| sp.as_mut().map(|sp| sp_conf.apply(sp.as_mut())));
+      if let Some(Err(e)) = applied {
+        warn!(sp_conf = ?sp_conf, "{}", mk_err_str(&e, "串口参数设置失败"));
+        status(slint_f!("{}", mk_err_str(e, "串口参数设置失败")), true);
+        return false;
+      }
+      info!(sp_conf = ?sp_conf, "串口参数已更新");
+      save_sp_conf(&sp_conf);
       return true;
     };
+    if gone {
+      warn!(sel_sp = ?sel_sp, "所选串口已移除");
+      status(slint_f!("所选串口已移除：{}", sel_sp.port_name), true);
+      return false;
+    }
+    if chat::peer_name().is_some_and(|name| name == sel_sp.port_name) {
+      warn!(sel_sp = ?sel_sp, "所选串口已作为对端串口打开");
+      status(slint_f!("所选串口已作为对端串口打开：{}", sel_sp.port_name), true);
+      return false;
+    }
+    open_sp(&sel_sp, &sp_conf)
+  });
+
+  app.global::<Options>().on_sp_close(|| close_sp(false));
+
+  app.global::<Options>().on_peer_open(|sel_sp_idx| {
+    let Some((sel_sp, gone)) = ALL_SPS.with_borrow(|sps| sps.get(sel_sp_idx as usize).cloned())
+    else {
+      return false;
+    };
+    if gone || CUR_SP_NAME.with_borrow(|name| Some(&sel_sp.port_name) == name.as_ref()) {
+      warn!(sel_sp = ?sel_sp, "所选串口不可用作对端串口");
+      status(slint_f!("所选串口不可用作对端串口：{}", sel_sp.port_name), true);
+      return false;
+    }
+    let sp_conf =
+      WEAK_APP.with(|w| sp_conf_from_ui(&w.get().unwrap().unwrap().global::<Options>()));
+    chat::open_peer(&sel_sp, &sp_conf)
+  });
+
+  app.global::<Options>().on_peer_close(chat::close_peer);
+
+  app.global::<Options>().on_cap_start(|path| {
+    if let Err(e) = CAPTURE.start(std::path::Path::new(path.as_str())) {
+      warn!(path = ?path, "{e}");
+      append_dp_text(slint_f!("抓包失败：{e}"));
+      return false;
+    }
+    info!(path = ?path, "开始抓包");
+    true
+  });
+
+  app.global::<Options>().on_cap_stop(|| {
+    CAPTURE.stop();
+    info!("停止抓包");
+  });
+
+  app.global::<Options>().on_replay_view(|path| {
+    let recs = match capture::load(std::path::Path::new(path.as_str())) {
+      Ok(recs) => recs,
+      Err(e) => {
+        warn!(path = ?path, "{e}");
+        append_dp_text(slint_f!("回放失败：{e}"));
+        return;
+      }
+    };
+    info!(path = ?path, recs = recs.len(), "离线回放");
+    append_dp_text(slint_f!("离线回放：{path}，共 {} 条记录", recs.len()));
+    let hub = Hub::new();
+    hub.subscribe(std::sync::Arc::new(LogSink));
+    WEAK_APP.with(|w| {
+      hub.subscribe(std::sync::Arc::new(display::AppSink::replay(w.get().unwrap().clone())))
+    });
+    replay::decode(&recs, &hub);
+  });
+
+  app.global::<Options>().on_cap_export(|path| {
+    let path = std::path::Path::new(path.as_str());
+    let exported = capture::load(path).and_then(|recs| {
+      let pcap_path = path.with_extension("pcapng");
+      let lua_path = path.with_file_name("uifs.lua");
+      let n = pcap::export(&recs, &pcap_path)?;
+      pcap::write_dissector(&lua_path)?;
+      Ok((n, pcap_path, lua_path))
+    });
+    match exported {
+      Ok((n, pcap_path, lua_path)) => {
+        info!(pcap_path = ?pcap_path, frms = n, "已导出 pcapng");
+        append_dp_text(slint_f!(
+          "已导出 {n} 帧：{}，解析器：{}",
+          pcap_path.display(),
+          lua_path.display()
+        ));
+      }
+      Err(e) => {
+        warn!(path = ?path, "{e}");
+        append_dp_text(slint_f!("导出失败：{e}"));
+      }
+    }
+  });
+
+  app.global::<Options>().on_replay_dev(|path| {
+    let recs = match capture::load(std::path::Path::new(path.as_str())) {
+      Ok(recs) => recs,
+      Err(e) => {
+        warn!(path = ?path, "{e}");
+        append_dp_text(slint_f!("回放失败：{e}"));
+        return;
+      }
+    };
+    let xchgs = replay::xchgs(&recs);
+    let Some(sp) = CUR_SP.with_borrow(|sp| sp.as_ref().map(|sp| sp.try_clone())) else {
+      warn!("回放失败：串口未打开");
+      status(slint_f!("回放失败：串口未打开"), true);
+      return;
+    };
+    let mut sp = match sp {
+      Ok(sp) => sp,
+      Err(e) => {
+        warn!("{}", mk_err_str(&e, "无法复制串口句柄"));
+        status(slint_f!("{}", mk_err_str(e, "无法复制串口句柄")), true);
+        return;
+      }
+    };
+    CUR_LSN_HNDLR.with_borrow_mut(|hndlr| hndlr.take().inspect(|h| h.abort()));
+    let weak_app = WEAK_APP.with(|w| w.get().unwrap().clone());
+    let opts = weak_app.unwrap();
+    let opts = opts.global::<Options>();
+    let wait = replay::REPLAY_WAIT.max(Durn::from_millis(opts.get_timeout_ms().max(0) as u64));
+    opts.set_replaying(true);
+    info!(path = ?path, xchgs = xchgs.len(), "开始回放");
+    opts.invoke_append_dp_text(slint_f!("回放到设备：{path}，共 {} 组交互", xchgs.len()));
+    tokio::task::spawn_blocking(move || {
+      let diffs = replay::run(sp.as_mut(), &xchgs, wait).map_err(|e| e.to_string());
+      invoke_from_event_loop(move || {
+        let app = weak_app.unwrap();
+        let opts = app.global::<Options>();
+        opts.set_replaying(false);
+        switch_mode(CUR_MODE.load(Relaxed));
+        let diffs = match diffs {
+          Ok(diffs) => diffs,
+          Err(e) => {
+            warn!("{e}");
+            opts.invoke_append_dp_text(slint_f!("回放中断：{e}"));
+            return;
+          }
+        };
+        for diff in &diffs {
+          opts.invoke_append_dp_text(slint_f!(
+            "第 {} 组不一致（偏移 {}）：发送 {}，期望 {}，实际 {}",
+            diff.idx + 1,
+            diff.offset(),
+            const_hex::encode(&diff.tx),
+            const_hex::encode(&diff.expected),
+            const_hex::encode(&diff.got)
+          ));
+        }
+        info!(xchgs = xchgs.len(), diffs = diffs.len(), "回放完成");
+        opts.invoke_append_dp_text(slint_f!(
+          "回放完成：共 {} 组交互，{} 组不一致",
+          xchgs.len(),
+          diffs.len()
+        ));
+      })
+      .unwrap();
+    });
+  });
 
-    let mut rst = false;
-
-    ALL_SPS.with(|oc| {
-      let all_sps = oc.get().unwrap();
-      let sel_sp = &all_sps[sel_sp_idx as usize];
-      match serialport::new(sel_sp.port_name.as_str(), SP_BAUD_RATE).timeout(SP_TIMEOUT).open() {
-        Ok(cur_sp) => {
-          let replace_sp = cur_sp.try_clone().unwrap();
-          CUR_SP.with_borrow_mut(|sp| {
-            sp.replace(replace_sp);
-          });
-          info!(sel_sp_idx = sel_sp_idx, sel_sp = ?sel_sp, "成功打开选择的串口");
-          WEAK_APP.with(|w| {
-            let w = w.get().unwrap().clone();
-            CUR_LSN_HNDLR.with(|hndlr| {
-              let lsn_task = tokio::spawn(receiver::lsn_sp(cur_sp, w));
-              hndlr.replace(Some(lsn_task)).inspect(|h| h.abort());
-            });
-          });
-
-          CUR_SP_IDX.store(sel_sp_idx, Relaxed);
-          rst = true;
+  app.global::<Bench>().on_run(|op_idx, frms, size, window| {
+    let Some(sp) = CUR_SP.with_borrow(|sp| sp.as_ref().map(|sp| sp.try_clone())) else {
+      warn!("基准测试失败：串口未打开");
+      status(slint_f!("基准测试失败：串口未打开"), true);
+      return;
+    };
+    let mut sp = match sp {
+      Ok(sp) => sp,
+      Err(e) => {
+        warn!("{}", mk_err_str(&e, "无法复制串口句柄"));
+        status(slint_f!("{}", mk_err_str(e, "无法复制串口句柄")), true);
+        return;
+      }
+    };
+    CUR_LSN_HNDLR.with_borrow_mut(|hndlr| hndlr.take().inspect(|h| h.abort()));
+    let weak_app = WEAK_APP.with(|w| w.get().unwrap().clone());
+    let app = weak_app.unwrap();
+    let opts = app.global::<Options>();
+    let conf = BenchConf {
+      op: match op_idx {
+        0 => BenchOp::Sm3,
+        1 => BenchOp::Sm4Ecb,
+        _ => BenchOp::Sm4Cbc,
+      },
+      frms: frms.max(1) as u32,
+      size: size.max(1) as usize,
+      window: window.max(0) as u32,
+      timeout: bench::BENCH_WAIT.max(Durn::from_millis(opts.get_timeout_ms().max(0) as u64)),
+    };
+    let sp_conf = sp_conf_from_ui(&opts);
+    app.global::<Bench>().set_running(true);
+    info!(conf = ?conf, "开始基准测试");
+    tokio::task::spawn_blocking(move || {
+      let rst = bench::run(sp.as_mut(), &conf, &sp_conf).map_err(|e| e.to_string());
+      invoke_from_event_loop(move || {
+        let app = weak_app.unwrap();
+        let bench = app.global::<Bench>();
+        bench.set_running(false);
+        if BenchOp::Sm3 != conf.op {
+          app.global::<Options>().set_key_ready(false);
+        }
+        switch_mode(CUR_MODE.load(Relaxed));
+        let rst = match rst {
+          Ok(rst) => rst,
+          Err(e) => {
+            warn!("{e}");
+            bench.set_result(slint_f!("测试中断：{e}"));
+            return;
+          }
+        };
+        info!(rst = ?rst, "基准测试完成");
+        bench.set_result(slint_f!(
+          "{} × {} B：成功 {}，错误 {}，丢失 {}，耗时 {:.3} s\n\
+           设备吞吐 {:.4} Mbit/s，串口上限 {:.4} Mbit/s，利用率 {:.1}%",
+          conf.op.name(),
+          conf.size,
+          rst.ok,
+          rst.bad,
+          rst.lost,
+          rst.elapsed.as_secs_f64(),
+          rst.device_mbps,
+          rst.bound_mbps,
+          rst.utilisation() * 100.0
+        ));
+        bench.set_has_result(true);
+        LAST_BENCH.with_borrow_mut(|last| *last = Some(rst));
+      })
+      .unwrap();
+    });
+  });
+
+  app.global::<Bench>().on_save(|path, label| {
+    LAST_BENCH.with_borrow(|last| {
+      let Some(rst) = last else { return };
+      let app = WEAK_APP.with(|w| w.get().unwrap().unwrap());
+      match rst.save(std::path::Path::new(path.as_str()), &label) {
+        Ok(()) => {
+          info!(path = ?path, "测试结果已保存");
+          app
+            .global::<Bench>()
+            .set_result(slint_f!("{}\n已保存到 {path}", app.global::<Bench>().get_result()));
         }
         Err(e) => {
-          warn!(sel_sp_idx = sel_sp_idx, sel_sp = ?sel_sp, "{}", mk_err_str(e, "无法打开此串口"));
+          warn!(path = ?path, "{e}");
+          app
+            .global::<Bench>()
+            .set_result(slint_f!("{}\n保存失败：{e}", app.global::<Bench>().get_result()));
         }
-      };
+      }
     });
-    rst
+  });
+
+  app.global::<Latency>().on_reset(|| {
+    LATENCY.reset();
+    let app = WEAK_APP.with(|w| w.get().unwrap().unwrap());
+    app.global::<Latency>().set_ops(ModelRc::from(latency_to_ui().as_slice()));
+  });
+
+  app.global::<Options>().on_stats_reset(|| {
+    STATS.reset();
+    let opts = WEAK_APP.with(|w| w.get().unwrap().unwrap());
+    opts.global::<Options>().set_stats(stats_to_ui(&Counts::default(), 0.0));
   });
 
   app.global::<Options>().on_lsn(|| {
     if 0 != CUR_MODE.load(Relaxed) {
-      CUR_SP.with_borrow_mut(|sp| {
-        if let Some(sp) = sp.as_mut() {
-          let sp = sp.try_clone().unwrap();
-          WEAK_APP.with(|w| {
-            let w = w.get().unwrap().clone();
-            CUR_LSN_HNDLR.with(|hndlr| {
-              let lsn_task = tokio::spawn(receiver::lsn_sp(sp, w));
-              hndlr.replace(Some(lsn_task)).inspect(|h| h.abort());
-            });
-          });
-          CUR_MODE.store(0, Relaxed);
-        }
-      });
+      switch_mode(0);
     }
   });
 
   app.global::<Options>().on_parse(|chat| {
-    CUR_SP.with_borrow_mut(|sp| {
-      if let Some(sp) = sp.as_mut() {
-        let sp = sp.try_clone().unwrap();
-        WEAK_APP.with(|w| {
-          let w = w.get().unwrap().clone();
-          CUR_LSN_HNDLR.with(|hndlr| {
-            let lsn_task = tokio::spawn(receiver::parse_sp(sp, w, chat));
-            hndlr.replace(Some(lsn_task)).inspect(|h| h.abort());
-          });
-        });
-        CUR_MODE.store(1, Relaxed);
-      }
-    });
+    WEAK_APP.with(|w| w.get().unwrap().unwrap().global::<Options>().set_chat(chat));
+    switch_mode(1);
   });
 
   app.global::<Options>().on_obsr(|| {
     if 2 != CUR_MODE.load(Relaxed) {
-      CUR_SP.with_borrow_mut(|sp| {
-        if let Some(sp) = sp.as_mut() {
-          let sp = sp.try_clone().unwrap();
-          WEAK_APP.with(|w| {
-            let w = w.get().unwrap().clone();
-            CUR_LSN_HNDLR.with(|hndlr| {
-              let lsn_task = tokio::spawn(receiver::obsr_sp(sp, w));
-              hndlr.replace(Some(lsn_task)).inspect(|h| h.abort());
-            });
-          });
-          CUR_MODE.store(2, Relaxed);
-        }
-      });
+      switch_mode(2);
     }
   });
 
   app.global::<Options>().on_key_send(|k| {
-    let bytes = k.as_bytes();
-    if 32 != bytes.len() {
-      warn!(key_len = bytes.len(), "输入密钥长不为 128 位");
-      return;
-    }
-    if let Err(e) = const_hex::check(bytes) {
-      warn!(key = ?k, "{}", mk_err_str(e, "出现非密钥字符"));
-      return;
-    }
-    let bytes = const_hex::decode(k.clone()).unwrap();
+    let Some(bytes) = field(Field::Key, &k, "密钥", check::blk) else { return };
 
-    let send_key = key(bytes.as_slice().try_into().unwrap());
+    let send_key = key(&bytes);
 
     debug!(send_key = ?send_key, "发送密钥");
 
-    CUR_SP.with_borrow_mut(|cur_sp| {
-      if let Err(e) = cur_sp.as_mut().unwrap().write_all(&send_key.as_ref()) {
-        warn!(cur_sp = ?cur_sp, "{}", mk_err_str(e, "密钥发送失败"));
-      } else {
-        WEAK_APP.with(|w| {
-          let w = w.get().unwrap().clone();
-          invoke_from_event_loop(move || {
-            w.unwrap().global::<Options>().invoke_append_dp_text(slint_f!("{}：{}", "密钥注入", k));
-          })
-          .unwrap();
-        })
-      }
-    })
+    if chat::peer_name().is_some() {
+      chat::write_peer(&send_key, "对端密钥发送失败");
+    }
+    send_frm(OpFlag::Key, send_key, "密钥发送失败", slint_f!("{}：{}", "密钥注入", k));
   });
 
+  app.global::<Options>().on_gen_key(|| gen_blk(Field::Key, "密钥"));
+
+  app.global::<Options>().on_gen_iv(|| gen_blk(Field::Iv, "初始向量"));
+
+  keys::init(&app);
+
   app.global::<Options>().on_send_test(|msg| {
-    if msg.len() > TX_MSG_MAX_LEN {
-      warn!(msg_len = msg.len(), "消息过长");
+    let app = WEAK_APP.with(|w| w.get().unwrap().unwrap());
+    let opts = app.global::<Options>();
+    let end = LineEnd::from_idx(opts.get_line_end()).bytes();
+    let Some(mut data) = field(Field::Msg, &msg, "消息", |d| check::len(d, end.len())) else {
       return;
     };
-    debug!(msg = ?msg.as_bytes(), "发送测试消息");
-    CUR_SP.with_borrow_mut(|cur_sp| {
-      if let Err(e) = cur_sp.as_mut().unwrap().write_all(msg.as_bytes()) {
-        warn!(cur_sp = ?cur_sp, "{}", mk_err_str(e, "测试消息发送失败"));
-      } else {
-        WEAK_APP.with(|w| {
-          let w = w.get().unwrap().clone();
-          invoke_from_event_loop(move || {
-            w.unwrap().global::<Options>().invoke_append_dp_text(slint_f!(
-              "{}：{}",
-              "测试消息",
-              msg
-            ));
-          })
-          .unwrap();
-        })
+    data.extend_from_slice(end);
+    debug!(msg = ?data, "发送测试消息");
+    if opts.get_loopback() {
+      let missing = LOOPBACK.expect(&data);
+      if 0 != missing {
+        append_dp_text(slint_f!("自检：上次发送尚有 {missing} 字节未回显"));
       }
-    });
+    }
+    if write_sp(&data, "测试消息发送失败") {
+      append_dp_text(slint_f!("{}：{}", "测试消息", msg));
+    }
   });
 
-  app.global::<Options>().on_send_sm3(|msg| {
-    if msg.len() > TX_MSG_MAX_LEN {
-      warn!(msg_len = msg.len(), "消息过长");
+  app.global::<Options>().on_loopback_reset(|| LOOPBACK.reset());
+
+  app.global::<Options>().on_file_run(|op_idx, mode_idx, iv, input, output| {
+    let mode = if 1 == mode_idx { BlockMode::CBC } else { BlockMode::ECB };
+    let iv = match mode {
+      BlockMode::ECB => [0; uifs_app::IV_LEN],
+      BlockMode::CBC => {
+        let Some(iv) = field(Field::Iv, &iv, "初始向量", check::blk) else { return };
+        iv
+      }
+    };
+    let Some(sp) = CUR_SP.with_borrow(|sp| sp.as_ref().map(|sp| sp.try_clone())) else {
+      warn!("文件处理失败：串口未打开");
+      status(slint_f!("文件处理失败：串口未打开"), true);
       return;
     };
+    let mut sp = match sp {
+      Ok(sp) => sp,
+      Err(e) => {
+        warn!("{}", mk_err_str(&e, "无法复制串口句柄"));
+        status(slint_f!("{}", mk_err_str(e, "无法复制串口句柄")), true);
+        return;
+      }
+    };
+    CUR_LSN_HNDLR.with_borrow_mut(|hndlr| hndlr.take().inspect(|h| h.abort()));
+    let weak_app = WEAK_APP.with(|w| w.get().unwrap().clone());
+    let app = weak_app.unwrap();
+    let opts = app.global::<Options>();
+    let job = FileJob {
+      op: match op_idx {
+        1 => FileOp::Sm3,
+        2 => FileOp::Sm4Enc(mode),
+        _ => FileOp::Sm4Dec(mode),
+      },
+      input: input.as_str().into(),
+      output: (!output.is_empty()).then(|| output.as_str().into()),
+      iv,
+      timeout: Durn::from_millis(opts.get_timeout_ms().max(0) as u64),
+    };
+    FILE_CANCEL.store(false, Relaxed);
+    opts.set_file_progress(0.0);
+    opts.set_file_running(true);
+    info!(job = ?job, "开始处理文件");
+    opts.invoke_append_dp_text(slint_f!("处理文件：{input}"));
+    tokio::task::spawn_blocking(move || {
+      let progress_app = weak_app.clone();
+      let rst = file::run(sp.as_mut(), &job, &HUB, &FILE_CANCEL, |done, total| {
+        let progress = if 0 == total { 1.0 } else { done as f32 / total as f32 };
+        let _ = progress_app
+          .upgrade_in_event_loop(move |app| app.global::<Options>().set_file_progress(progress));
+      })
+      .map_err(|e| e.to_string());
+      invoke_from_event_loop(move || {
+        let app = weak_app.unwrap();
+        let opts = app.global::<Options>();
+        opts.set_file_running(false);
+        switch_mode(CUR_MODE.load(Relaxed));
+        let line = match rst {
+          Ok(FileRst::Hash(hash)) => slint_f!("文件 SM3 结果：{}", const_hex::encode(hash)),
+          Ok(FileRst::Written(n)) => {
+            slint_f!("文件处理完成：已写入 {n} 字节到 {}", output)
+          }
+          Ok(FileRst::Cancelled(n)) => slint_f!("文件处理已取消：已写入 {n} 字节"),
+          Err(e) => {
+            warn!("{e}");
+            slint_f!("文件处理失败：{e}")
+          }
+        };
+        info!("{line}");
+        opts.invoke_append_dp_text(line);
+      })
+      .unwrap();
+    });
+  });
 
-    let send_msg = sm3(msg.as_bytes());
+  app.global::<Options>().on_file_cancel(|| FILE_CANCEL.store(true, Relaxed));
+
+  app.global::<Terminal>().on_key(|text, ctrl, alt| {
+    let data = terminal::key_bytes(&text, ctrl, alt);
+    if !data.is_empty() {
+      write_sp(&data, "终端输入发送失败");
+    }
+  });
+
+  app.global::<Terminal>().on_set_raw(|raw| {
+    info!(raw, "切换终端直通");
+    RAW.store(raw, Relaxed);
+    switch_mode(CUR_MODE.load(Relaxed));
+  });
+
+  app.global::<Options>().on_send_sm3(|msg| {
+    let Some(bytes) = field(Field::Msg, &msg, "消息", |d| check::len(d, 0)) else { return };
+
+    let send_msg = sm3(&bytes);
     info!(send_msg = ?send_msg, "发送 SM3 消息");
 
-    CUR_SP.with_borrow_mut(|cur_sp| {
-      if let Err(e) = cur_sp.as_mut().unwrap().write_all(send_msg.as_ref()) {
-        warn!(cur_sp = ?cur_sp, "{}", mk_err_str(e, "消息发送失败"));
-      } else {
-        WEAK_APP.with(|w| {
-          let w = w.get().unwrap().clone();
-          invoke_from_event_loop(move || {
-            w.unwrap().global::<Options>().invoke_append_dp_text(slint_f!(
-              "{}：{}",
-              "消息",
-              msg
-            ));
-          })
-          .unwrap();
-        })
-      }
-    });
+    send_frm(OpFlag::Sm3, send_msg, "消息发送失败", slint_f!("{}：{}", "消息", msg));
   });
 
   app.global::<Options>().on_send_sm4e_cbc(|pt, iv| {
-    if 32 != iv.len() {
-      warn!(iv = ?iv, "输入初始向量长不为 128 位");
-      return;
-    };
-    let iv = const_hex::decode(iv).unwrap();
+    let Some(iv) = field(Field::Iv, &iv, "初始向量", check::blk) else { return };
+    let Some(pt_bytes) = field(Field::Msg, &pt, "明文", |d| check::pt(d, true)) else { return };
 
-    let send_pt = sm4_enc_cbc(pt.as_bytes().try_into().unwrap(), iv.as_slice().try_into().unwrap());
+    let send_pt = sm4_enc_cbc(&pt_bytes, &iv);
     debug!(send_pt =? send_pt);
-    CUR_SP.with_borrow_mut(|cur_sp| {
-      if let Err(e) = cur_sp.as_mut().unwrap().write_all(&send_pt) {
-        warn!(cur_sp = ?cur_sp, "{}", mk_err_str(e, "CBC 加密请求发送失败"));
-      } else {
-        WEAK_APP.with(|w| {
-          let w = w.get().unwrap().clone();
-          invoke_from_event_loop(move || {
-            w.unwrap().global::<Options>().invoke_append_dp_text(slint_f!(
-              "{}：{}",
-              "明文",
-              pt
-            ));
-          })
-          .unwrap();
-        })
-      }
-    })
+    send_frm(OpFlag::Sm4Enc, send_pt, "CBC 加密请求发送失败", slint_f!("{}：{}", "明文", pt));
   });
 
   app.global::<Options>().on_send_sm4e_ecb(|pt| {
-    let send_pt = sm4_enc_ecb(pt.as_bytes());
+    let Some(pt_bytes) = field(Field::Msg, &pt, "明文", |d| check::pt(d, false)) else { return };
+    let send_pt = sm4_enc_ecb(&pt_bytes);
     debug!(send_pt =? send_pt[..]);
 
-    CUR_SP.with_borrow_mut(|cur_sp| {
-      if let Err(e) = cur_sp.as_mut().unwrap().write_all(&send_pt) {
-        warn!(cur_sp = ?cur_sp, "{}", mk_err_str(e, "ECB 加密请求发送失败"));
-      } else {
-        WEAK_APP.with(|w| {
-          let w = w.get().unwrap().clone();
-          invoke_from_event_loop(move || {
-            w.unwrap().global::<Options>().invoke_append_dp_text(slint_f!(
-              "{}：{}",
-              "明文",
-              pt
-            ));
-          })
-          .unwrap();
-        })
-      };
-    })
+    send_frm(OpFlag::Sm4Enc, send_pt, "ECB 加密请求发送失败", slint_f!("{}：{}", "明文", pt));
   });
 
   app.global::<Options>().on_chat_send_sm4e_ecb(|pt| {
-    let send_pt = sm4_enc_ecb(pt.as_bytes());
+    let Some(pt_bytes) = field(Field::Msg, &pt, "明文", |d| check::pt(d, false)) else { return };
+    let send_pt = sm4_enc_ecb(&pt_bytes);
     debug!(send_pt =? send_pt[..]);
 
-    CUR_SP.with_borrow_mut(|cur_sp| {
-      if let Err(e) = cur_sp.as_mut().unwrap().write_all(&send_pt) {
-        warn!(cur_sp = ?cur_sp, "{}", mk_err_str(e, "ECB 加密请求发送失败"));
-      } else {
-        WEAK_APP.with(|w| {
-          let w = w.get().unwrap().clone();
-          invoke_from_event_loop(move || {
-            w.unwrap().global::<Options>().invoke_append_dp_text(slint_f!(
-              "{}：{}",
-              if w.unwrap().global::<Options>().get_name() { "Bob" } else { "Alice" },
-              pt
-            ));
-          })
-          .unwrap();
-        })
-      };
-    })
+    let name = WEAK_APP.with(|w| w.get().unwrap().unwrap().global::<Options>().get_name());
+    let line = slint_f!("{}：{}", if name { "Bob" } else { "Alice" }, pt);
+    send_frm(OpFlag::Sm4Enc, send_pt, "ECB 加密请求发送失败", line);
   });
 
-  app.global::<Options>().on_send_sm4d_cbc(|ct, iv| {
-    if 32 != iv.len() {
-      let e = "输入初始向量长不为 128 位";
-      warn!("{e}");
+  app.global::<Options>().on_chat_send(|bob, pt| {
+    if let Err(e) = check::pt(pt.as_bytes().to_vec(), false) {
+      warn!(msg_len = pt.len(), "消息{e}");
+      status(slint_f!("消息{e}"), true);
       return;
     };
-    let ct_bytes = const_hex::decode(ct.clone()).unwrap();
-    let iv = const_hex::decode(iv).unwrap();
-    let send_ct = sm4_dec_cbc(ct_bytes.as_slice(), iv.as_slice().try_into().unwrap());
+    chat::send(bob, &pt);
+  });
+
+  app.global::<Options>().on_send_sm4d_cbc(|ct, iv| {
+    let Some(iv) = field(Field::Iv, &iv, "初始向量", check::blk) else { return };
+    let Some(ct_bytes) = field(Field::Msg, &ct, "密文", |d| check::ct(d, true)) else { return };
+    let send_ct = sm4_dec_cbc(&ct_bytes, &iv);
     debug!(send_ct =? send_ct);
-    CUR_SP.with_borrow_mut(|cur_sp| {
-      if let Err(e) = cur_sp.as_mut().unwrap().write_all(&send_ct) {
-        error!(cur_sp = ?cur_sp, "{}", mk_err_str(e, "CBC 解密请求发送失败"));
-      } else {
-        WEAK_APP.with(|w| {
-          let w = w.get().unwrap().clone();
-          invoke_from_event_loop(move || {
-            w.unwrap().global::<Options>().invoke_append_dp_text(slint_f!(
-              "{}：{}",
-              "密文",
-              ct
-            ));
-          })
-          .unwrap();
-        })
-      }
-    })
+    send_frm(OpFlag::Sm4Dec, send_ct, "CBC 解密请求发送失败", slint_f!("{}：{}", "密文", ct));
   });
 
   app.global::<Options>().on_send_sm4d_ecb(|ct| {
-    let ct_bytes = const_hex::decode(ct.clone()).unwrap();
-    let send_ct = sm4_dec_ecb(ct_bytes.as_slice());
+    let Some(ct_bytes) = field(Field::Msg, &ct, "密文", |d| check::ct(d, false)) else { return };
+    let send_ct = sm4_dec_ecb(&ct_bytes);
     debug!(send_ct =? send_ct);
-    CUR_SP.with_borrow_mut(|cur_sp| {
-      if let Err(e) = cur_sp.as_mut().unwrap().write_all(&send_ct) {
-        error!(cur_sp = ?cur_sp, "{}", mk_err_str(e, "ECB 解密请求发送失败"));
-      } else {
-        WEAK_APP.with(|w| {
-          let w = w.get().unwrap().clone();
-          invoke_from_event_loop(move || {
-            w.unwrap().global::<Options>().invoke_append_dp_text(slint_f!(
-              "{}：{}",
-              "密文",
-              ct
-            ));
-          })
-          .unwrap();
-        })
-      }
-    })
+    send_frm(OpFlag::Sm4Dec, send_ct, "ECB 解密请求发送失败", slint_f!("{}：{}", "密文", ct));
   });
 
   if let Err(e) = app.show() {
@@ -392,7 +786,420 @@ async fn main() -> Rst<()> {
     we!("{e}");
   };
 
+  settings::save_session(&app);
+
   trace!("应用结束");
 
   Ok(())
 }
+
+fn append_dp_text(line: SlintStr) {
+  WEAK_APP.with(|w| {
+    let w = w.get().unwrap().clone();
+    invoke_from_event_loop(move || w.unwrap().global::<Options>().invoke_append_dp_text(line))
+      .unwrap();
+  })
+}
+
+#[derive(Clone, Copy)]
+enum Field {
+  Msg,
+  Key,
+  Iv,
+}
+
+fn status(line: SlintStr, err: bool) {
+  let app = WEAK_APP.with(|w| w.get().unwrap().unwrap());
+  app.global::<Status>().set_text(line);
+  app.global::<Status>().set_err(err);
+}
+
+fn field<T>(
+  fld: Field,
+  input: &str,
+  name: &str,
+  check: impl FnOnce(Vec<u8>) -> Rst<T, Str>,
+) -> Opt<T> {
+  let app = WEAK_APP.with(|w| w.get().unwrap().unwrap());
+  let opts = app.global::<Options>();
+  let (enc, set_err): (_, fn(&Options<'_>, SlintStr)) = match fld {
+    Field::Msg => (opts.get_msg_enc(), |opts, e| opts.set_msg_err(e)),
+    Field::Key => (opts.get_key_enc(), |opts, e| opts.set_key_err(e)),
+    Field::Iv => (opts.get_iv_enc(), |opts, e| opts.set_iv_err(e)),
+  };
+  match Codec::from_idx(enc).decode(input).map_err(|e| e.to_string()).and_then(check) {
+    Ok(data) => {
+      set_err(&opts, SlintStr::new());
+      Some(data)
+    }
+    Err(e) => {
+      warn!(input, "{name}：{e}");
+      set_err(&opts, slint_f!("{name}：{e}"));
+      status(slint_f!("{name}：{e}"), true);
+      None
+    }
+  }
+}
+
+fn gen_blk(fld: Field, name: &str) -> SlintStr {
+  let blk = match soft::random::<16>() {
+    Ok(blk) => blk,
+    Err(e) => {
+      warn!("{name}生成失败：{e}");
+      status(slint_f!("{name}生成失败：{e}"), true);
+      return SlintStr::new();
+    }
+  };
+  info!("已生成随机{name}");
+  status(slint_f!("已生成随机{name}"), false);
+  blk_text(fld, &blk)
+}
+
+fn blk_text(fld: Field, blk: &[u8]) -> SlintStr {
+  let app = WEAK_APP.with(|w| w.get().unwrap().unwrap());
+  let opts = app.global::<Options>();
+  let (enc, set_enc): (_, fn(&Options<'_>, i32)) = match fld {
+    Field::Key => (opts.get_key_enc(), |opts, enc| opts.set_key_enc(enc)),
+    _ => (opts.get_iv_enc(), |opts, enc| opts.set_iv_enc(enc)),
+  };
+  let codec = match Codec::from_idx(enc) {
+    Codec::Utf8 => {
+      set_enc(&opts, 1);
+      Codec::Hex
+    }
+    codec => codec,
+  };
+  codec.encode(blk).into()
+}
+
+fn write_sp(data: &[u8], desc: &str) -> bool {
+  CUR_SP.with_borrow_mut(|cur_sp| {
+    let Some(sp) = cur_sp.as_mut() else {
+      warn!("{desc}：串口未打开");
+      status(slint_f!("{desc}：串口未打开"), true);
+      return false;
+    };
+    if let Err(e) = sp.write_all(data) {
+      warn!(cur_sp = ?cur_sp, "{}", mk_err_str(&e, desc));
+      status(slint_f!("{}", mk_err_str(&e, desc)), true);
+      if std::io::ErrorKind::TimedOut != e.kind() {
+        HUB.emit(&Evt::Disconnected(mk_err_str(e, desc)));
+      }
+      false
+    } else {
+      HUB.emit(&Evt::Tx(Bytes::copy_from_slice(data)));
+      status(slint_f!("已发送 {} 字节", data.len()), false);
+      true
+    }
+  })
+}
+
+fn spawn_lsn(sp: Box<dyn SerialPort>) {
+  let lsn_task = match CUR_MODE.load(Relaxed) {
+    _ if RAW.load(Relaxed) => tokio::spawn(receiver::raw_sp(sp, &HUB)),
+    0 => tokio::spawn(receiver::lsn_sp(sp, &HUB)),
+    1 => tokio::spawn(receiver::parse_sp(sp, &HUB)),
+    _ => tokio::spawn(receiver::obsr_sp(sp, &HUB)),
+  };
+  CUR_LSN_HNDLR.with_borrow_mut(|hndlr| hndlr.replace(lsn_task).inspect(|h| h.abort()));
+  PENDING.clear();
+}
+
+fn switch_mode(mode: u8) {
+  CUR_MODE.store(mode, Relaxed);
+  let Some(sp) = CUR_SP.with_borrow(|sp| sp.as_ref().map(|sp| sp.try_clone())) else { return };
+  match sp {
+    Ok(sp) => spawn_lsn(sp),
+    Err(e) => warn!("{}", mk_err_str(e, "无法复制串口句柄")),
+  }
+}
+
+fn open_sp(sel_sp: &SerialPortInfo, sp_conf: &SpConf) -> bool {
+  let cur_sp = match sp_conf.builder(sel_sp.port_name.as_str()).open() {
+    Ok(cur_sp) => cur_sp,
+    Err(e) => {
+      warn!(sel_sp = ?sel_sp, "{}", mk_err_str(&e, "无法打开此串口"));
+      status(slint_f!("{}", mk_err_str(e, "无法打开此串口")), true);
+      return false;
+    }
+  };
+  let lsn_sp = match cur_sp.try_clone() {
+    Ok(lsn_sp) => lsn_sp,
+    Err(e) => {
+      warn!(sel_sp = ?sel_sp, "{}", mk_err_str(e, "无法复制串口句柄"));
+      return false;
+    }
+  };
+  close_sp(false);
+  info!(sel_sp = ?sel_sp, sp_conf = ?sp_conf, "成功打开选择的串口");
+  CUR_SP.set(Some(cur_sp));
+  CUR_SP_NAME.set(Some(sel_sp.port_name.clone()));
+  LOST_SP.set(None);
+  spawn_lsn(lsn_sp);
+  save_sp_conf(sp_conf);
+  WEAK_APP.with(|w| w.get().unwrap().unwrap().global::<Options>().set_sp_connected(true));
+  true
+}
+
+fn close_sp(lost: bool) {
+  CUR_LSN_HNDLR.with_borrow_mut(|hndlr| hndlr.take().inspect(|h| h.abort()));
+  CUR_SP.take();
+  PENDING.clear();
+  let Some(name) = CUR_SP_NAME.take() else { return };
+  let lost_sp = lost
+    .then(|| ALL_SPS.with_borrow(|sps| sps.iter().find(|(sp, _)| sp.port_name == name).cloned()))
+    .flatten()
+    .map(|(sp, _)| sp);
+  info!(name = name, lost_sp = ?lost_sp, "串口已关闭");
+  LOST_SP.set(lost_sp);
+  WEAK_APP.with(|w| w.get().unwrap().unwrap().global::<Options>().set_sp_connected(false));
+}
+
+fn same_dev(lost: &SerialPortInfo, sp: &SerialPortInfo) -> bool {
+  match (&lost.port_type, &sp.port_type) {
+    (SerialPortType::UsbPort(lost_usb), SerialPortType::UsbPort(usb))
+      if lost_usb.serial_number.is_some() =>
+    {
+      (lost_usb.vid, lost_usb.pid, &lost_usb.serial_number)
+        == (usb.vid, usb.pid, &usb.serial_number)
+    }
+    _ => lost.port_name == sp.port_name,
+  }
+}
+
+fn try_reconnect(opts: &Options<'_>) {
+  if !opts.get_auto_reconnect() || CUR_SP_NAME.with_borrow(Opt::is_some) {
+    return;
+  }
+  let Some(lost_sp) = LOST_SP.with_borrow(Clone::clone) else { return };
+  let Some((idx, sp)) = ALL_SPS.with_borrow(|sps| {
+    sps
+      .iter()
+      .enumerate()
+      .find(|(_, (sp, gone))| !gone && same_dev(&lost_sp, sp))
+      .map(|(idx, (sp, _))| (idx, sp.clone()))
+  }) else {
+    return;
+  };
+  if open_sp(&sp, &sp_conf_from_ui(opts)) {
+    opts.set_sp_idx(idx as i32);
+    opts.invoke_append_dp_text(slint_f!("已自动重连：{}", sp.port_name));
+  }
+}
+
+fn send_frm(op: OpFlag, frm: Bytes, desc: &str, line: SlintStr) {
+  let (timeout_ms, retries) = WEAK_APP.with(|w| {
+    let opts = w.get().unwrap().unwrap();
+    let opts = opts.global::<Options>();
+    (opts.get_timeout_ms(), opts.get_retries())
+  });
+  PENDING.track(
+    op,
+    frm.clone(),
+    Durn::from_millis(timeout_ms.max(0) as u64),
+    retries.max(0) as u32,
+  );
+  if !write_sp(&frm, desc) {
+    PENDING.untrack(op);
+    return;
+  }
+  PENDING.sent(op, Instant::now());
+  append_dp_text(line);
+}
+
+fn list_sps() -> Rst<Vec<SerialPortInfo>> {
+  let mut new_sps = match serialport::available_ports() {
+    Ok(sps) => sps,
+    Err(e) => {
+      we!("{}", mk_err_str(e, "无法获取可用串口"));
+    }
+  };
+  if let Ok(extra_sps) = std::env::var("UIFS_EXTRA_SPS") {
+    new_sps.extend(extra_sps.split(',').filter(|name| !name.is_empty()).map(|name| {
+      SerialPortInfo { port_name: name.to_string(), port_type: SerialPortType::Unknown }
+    }));
+  }
+  Ok(new_sps)
+}
+
+async fn scan_sps() {
+  let sps = tokio::task::spawn_blocking(|| list_sps().map_err(|e| e.to_string())).await;
+  invoke_from_event_loop(move || match sps {
+    Ok(Ok(sps)) => apply_sps(sps),
+    Ok(Err(e)) => warn!("{e}"),
+    Err(e) => warn!("{}", mk_err_str(e, "串口查询任务失败")),
+  })
+  .unwrap();
+}
+
+fn apply_sps(new_sps: Vec<SerialPortInfo>) {
+  let mut new_sps: Vec<_> = new_sps.into_iter().map(|sp| (sp, false)).collect();
+
+  let app = WEAK_APP.with(|w| w.get().unwrap().unwrap());
+  let opts = app.global::<Options>();
+  let idx_name =
+    |idx: i32| ALL_SPS.with_borrow(|sps| sps.get(idx as usize).map(|(sp, _)| sp.port_name.clone()));
+  let sel_name = idx_name(opts.get_sp_idx());
+  let peer_sel_name = idx_name(opts.get_peer_idx());
+  let cur_name = CUR_SP_NAME.with_borrow(Clone::clone);
+  let peer_name = chat::peer_name();
+  ALL_SPS.with_borrow(|old_sps| {
+    for (sp, _) in old_sps {
+      let keep = [&sel_name, &peer_sel_name, &cur_name, &peer_name]
+        .into_iter()
+        .any(|name| Some(&sp.port_name) == name.as_ref());
+      if keep && !new_sps.iter().any(|(new_sp, _)| new_sp.port_name == sp.port_name) {
+        new_sps.push((sp.clone(), true));
+      }
+    }
+  });
+  if cur_name.is_some_and(|name| new_sps.iter().any(|(sp, gone)| *gone && sp.port_name == name)) {
+    HUB.emit(&Evt::Disconnected(Str::from("设备已移除")));
+  }
+  if peer_name.is_some_and(|name| new_sps.iter().any(|(sp, gone)| *gone && sp.port_name == name)) {
+    chat::PEER_HUB.emit(&Evt::Disconnected(Str::from("对端设备已移除")));
+  }
+  if ALL_SPS.with_borrow(|old_sps| *old_sps != new_sps) {
+    update_sps(&opts, new_sps, sel_name, peer_sel_name);
+  }
+  try_reconnect(&opts);
+}
+
+fn update_sps(
+  opts: &Options<'_>,
+  new_sps: Vec<(SerialPortInfo, bool)>,
+  sel_name: Opt<Str>,
+  peer_sel_name: Opt<Str>,
+) {
+  debug!(new_sps = ?new_sps, "可用串口查询成功");
+
+  let slint_sps: Vec<_> = new_sps
+    .iter()
+    .map(|(sp, gone)| {
+      let gone = if *gone { "（已移除）" } else { "" };
+      if let SerialPortType::UsbPort(_) = sp.port_type {
+        slint_f!("{} UsbPort{gone}", sp.port_name)
+      } else {
+        slint_f!("{} {:?}{gone}", sp.port_name, sp.port_type)
+      }
+    })
+    .collect();
+  let idx_of = |name: Opt<Str>| {
+    name
+      .and_then(|name| new_sps.iter().position(|(sp, _)| sp.port_name == name))
+      .unwrap_or_default()
+  };
+  let (sel_idx, peer_sel_idx) = (idx_of(sel_name), idx_of(peer_sel_name));
+  opts.set_sps(ModelRc::from(slint_sps.as_slice()));
+  opts.set_sp_idx(sel_idx as i32);
+  opts.set_peer_idx(peer_sel_idx as i32);
+  ALL_SPS.set(new_sps);
+  trace!("串口列表设置成功");
+}
+
+fn sp_conf_to_ui(opts: &Options<'_>, sp_conf: &SpConf) {
+  opts.set_sp_baud_rate(slint_f!("{}", sp_conf.baud_rate));
+  opts.set_sp_data_bits(match sp_conf.data_bits {
+    DataBits::Five => 0,
+    DataBits::Six => 1,
+    DataBits::Seven => 2,
+    DataBits::Eight => 3,
+  });
+  opts.set_sp_parity(match sp_conf.parity {
+    Parity::None => 0,
+    Parity::Odd => 1,
+    Parity::Even => 2,
+  });
+  opts.set_sp_stop_bits(match sp_conf.stop_bits {
+    StopBits::One => 0,
+    StopBits::Two => 1,
+  });
+  opts.set_sp_flow_ctrl(match sp_conf.flow_ctrl {
+    FlowControl::None => 0,
+    FlowControl::Software => 1,
+    FlowControl::Hardware => 2,
+  });
+  opts.set_sp_timeout_ms(sp_conf.timeout_ms as i32);
+}
+
+fn sp_conf_from_ui(opts: &Options<'_>) -> SpConf {
+  SpConf {
+    baud_rate: opts.get_sp_baud_rate().parse().unwrap_or(SP_BAUD_RATE),
+    data_bits: match opts.get_sp_data_bits() {
+      0 => DataBits::Five,
+      1 => DataBits::Six,
+      2 => DataBits::Seven,
+      _ => DataBits::Eight,
+    },
+    parity: match opts.get_sp_parity() {
+      1 => Parity::Odd,
+      2 => Parity::Even,
+      _ => Parity::None,
+    },
+    stop_bits: if 1 == opts.get_sp_stop_bits() { StopBits::Two } else { StopBits::One },
+    flow_ctrl: match opts.get_sp_flow_ctrl() {
+      1 => FlowControl::Software,
+      2 => FlowControl::Hardware,
+      _ => FlowControl::None,
+    },
+    timeout_ms: opts.get_sp_timeout_ms().max(0) as u64,
+  }
+}
+
+fn stats_to_ui(counts: &Counts, rate: f64) -> ModelRc<StatRow> {
+  let rows = [
+    ("发送字节", slint_f!("{}", counts.tx_bytes)),
+    ("接收字节", slint_f!("{}", counts.rx_bytes)),
+    ("解析帧数", slint_f!("{}", counts.frms)),
+    ("跳过字节", slint_f!("{}", counts.skipped)),
+    ("长度错误", slint_f!("{}", counts.len_errs)),
+    ("未知操作码", slint_f!("{}", counts.op_errs)),
+    ("校验错误", slint_f!("{}", counts.par_errs)),
+    ("其他帧错误", slint_f!("{}", counts.other_errs)),
+    ("超时", slint_f!("{}", counts.timeouts)),
+    ("吞吐量", slint_f!("{rate:.0} B/s")),
+  ];
+  let rows: Vec<_> =
+    rows.into_iter().map(|(name, value)| StatRow { name: name.into(), value }).collect();
+  ModelRc::from(rows.as_slice())
+}
+
+fn latency_to_ui() -> Vec<OpLatency> {
+  let ms = |d: Durn| d.as_secs_f64() * 1e3;
+  OpFlag::ALL
+    .into_iter()
+    .map(|op| {
+      let sorted = LATENCY.sorted(op);
+      let Some(sum) = LatencyStats::summary(&sorted) else {
+        return OpLatency {
+          name: display::op_name(op).into(),
+          summary: slint_f!("暂无数据"),
+          range: SlintStr::default(),
+          bins: ModelRc::default(),
+        };
+      };
+      let hist = LatencyStats::hist(&sorted);
+      let peak = hist.iter().copied().max().unwrap_or(1).max(1) as f32;
+      let bins: Vec<_> = hist.iter().map(|&n| n as f32 / peak).collect();
+      OpLatency {
+        name: display::op_name(op).into(),
+        summary: slint_f!(
+          "{} 次  最小 {:.3} ms  中位 {:.3} ms  P99 {:.3} ms  最大 {:.3} ms",
+          sum.n,
+          ms(sum.min),
+          ms(sum.median),
+          ms(sum.p99),
+          ms(sum.max)
+        ),
+        range: slint_f!("{:.3} ms ~ {:.3} ms，{} 组", ms(sum.min), ms(sum.max), latency::HIST_BINS),
+        bins: ModelRc::from(bins.as_slice()),
+      }
+    })
+    .collect()
+}
+
+fn save_sp_conf(sp_conf: &SpConf) {
+  if let Err(e) = conf::save(SpConf::FILE, sp_conf) {
+    warn!("{e}");
+  }
+}
@@ -0,0 +1,180 @@
+use crate::{f, Dbg, Opt, Str};
+use core::fmt::Write as _;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Write};
+
+pub const OBSR_MAX_POINTS: usize = 10_000;
+pub const PLOT_SIZE: f64 = 1000.0;
+
+#[derive(Clone, Copy, Dbg, PartialEq, num_enum::TryFromPrimitive)]
+#[repr(u8)]
+pub enum SampleTp {
+  U8 = 1,
+  U16 = 2,
+  U32 = 3,
+  I32 = 4,
+  F32 = 5,
+}
+
+impl SampleTp {
+  pub const fn size(self) -> usize {
+    match self {
+      Self::U8 => 1,
+      Self::U16 => 2,
+      Self::U32 | Self::I32 | Self::F32 => 4,
+    }
+  }
+
+  pub const fn name(self) -> &'static str {
+    match self {
+      Self::U8 => "u8",
+      Self::U16 => "u16",
+      Self::U32 => "u32",
+      Self::I32 => "i32",
+      Self::F32 => "f32",
+    }
+  }
+
+  fn decode(self, b: &[u8]) -> f64 {
+    match self {
+      Self::U8 => b[0] as f64,
+      Self::U16 => u16::from_be_bytes([b[0], b[1]]) as f64,
+      Self::U32 => u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64,
+      Self::I32 => i32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64,
+      Self::F32 => f32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64,
+    }
+  }
+}
+
+#[derive(Clone, Dbg, PartialEq)]
+pub struct Obsr {
+  pub ch: u8,
+  pub tp: SampleTp,
+  pub samples: Vec<f64>,
+}
+
+impl Obsr {
+  pub fn parse(payload: &[u8]) -> Opt<Self> {
+    let &[ch, tp, ref data @ ..] = payload else { return None };
+    let tp = SampleTp::try_from(tp).ok()?;
+    if data.is_empty() || 0 != data.len() % tp.size() {
+      return None;
+    }
+    Some(Self { ch, tp, samples: data.chunks_exact(tp.size()).map(|b| tp.decode(b)).collect() })
+  }
+
+  pub fn summary(&self) -> Str {
+    let samples: Vec<_> = self.samples.iter().map(|v| f!("{v}")).collect();
+    f!("通道 {}（{}）：{}", self.ch, self.tp.name(), samples.join(", "))
+  }
+}
+
+#[derive(Dbg)]
+pub struct Trace {
+  pub tp: SampleTp,
+  pub points: VecDeque<(f64, f64)>,
+}
+
+#[derive(Dbg, Default)]
+pub struct Traces {
+  chans: BTreeMap<u8, Trace>,
+}
+
+impl Traces {
+  pub const fn new() -> Self {
+    Self { chans: BTreeMap::new() }
+  }
+
+  pub fn push(&mut self, t: f64, obsr: &Obsr) {
+    let trace =
+      self.chans.entry(obsr.ch).or_insert_with(|| Trace { tp: obsr.tp, points: VecDeque::new() });
+    trace.tp = obsr.tp;
+    trace.points.extend(obsr.samples.iter().map(|&v| (t, v)));
+    let excess = trace.points.len().saturating_sub(OBSR_MAX_POINTS);
+    trace.points.drain(..excess);
+  }
+
+  pub fn clear(&mut self) {
+    self.chans.clear();
+  }
+
+  pub fn chans(&self) -> impl Iterator<Item = (u8, &Trace)> {
+    self.chans.iter().map(|(&ch, trace)| (ch, trace))
+  }
+
+  pub fn y_range(&self, t_start: f64) -> Opt<(f64, f64)> {
+    let vals = self.chans.values().flat_map(|trace| trace.points.iter());
+    let vals = vals.filter(|(t, v)| *t >= t_start && v.is_finite()).map(|(_, v)| *v);
+    let (lo, hi) =
+      vals.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| (lo.min(v), hi.max(v)));
+    match (lo.is_finite(), lo == hi) {
+      (false, _) => None,
+      (true, true) => Some((lo - 1.0, hi + 1.0)),
+      (true, false) => Some((lo, hi)),
+    }
+  }
+
+  pub fn path(trace: &Trace, t_end: f64, window: f64, (lo, hi): (f64, f64)) -> Str {
+    let t_start = t_end - window;
+    let mut cmds = Str::new();
+    for (t, v) in trace.points.iter().filter(|(t, v)| *t >= t_start && v.is_finite()) {
+      let x = (t - t_start) / window * PLOT_SIZE;
+      let y = (hi - v) / (hi - lo) * PLOT_SIZE;
+      let cmd = if cmds.is_empty() { 'M' } else { 'L' };
+      let _ = write!(cmds, "{cmd} {x:.1} {y:.1} ");
+    }
+    cmds
+  }
+
+  pub fn write_csv(&self, w: &mut impl Write) -> io::Result<()> {
+    let mut rows: Vec<_> = self
+      .chans
+      .iter()
+      .flat_map(|(ch, trace)| trace.points.iter().map(move |(t, v)| (*t, *ch, trace.tp, *v)))
+      .collect();
+    rows.sort_by(|a, b| a.0.total_cmp(&b.0));
+    writeln!(w, "time_s,channel,type,value")?;
+    for (t, ch, tp, v) in rows {
+      writeln!(w, "{t:.6},{ch},{},{v}", tp.name())?;
+    }
+    w.flush()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(ch: u8, tp: u8, data: &[u8]) -> Opt<Obsr> {
+    Obsr::parse(&[[ch, tp].as_slice(), data].concat())
+  }
+
+  #[test]
+  fn big_endian_samples() {
+    let cases: [(SampleTp, &[u8], Vec<f64>); 5] = [
+      (SampleTp::U8, &[0x01, 0xFF], vec![1.0, 255.0]),
+      (SampleTp::U16, &[0x01, 0x02, 0xFF, 0xFF], vec![258.0, 65535.0]),
+      (SampleTp::U32, &[0x01, 0x02, 0x03, 0x04], vec![16_909_060.0]),
+      (SampleTp::I32, &[0xFF, 0xFF, 0xFF, 0xFE, 0x00, 0x00, 0x01, 0x00], vec![-2.0, 256.0]),
+      (SampleTp::F32, &[0x3F, 0xC0, 0x00, 0x00, 0xC1, 0x20, 0x00, 0x00], vec![1.5, -10.0]),
+    ];
+    for (tp, data, samples) in cases {
+      assert_eq!(Some(Obsr { ch: 7, tp, samples }), parse(7, tp as u8, data), "{}", tp.name());
+    }
+  }
+
+  #[test]
+  fn truncated() {
+    assert_eq!(None, Obsr::parse(&[]));
+    assert_eq!(None, Obsr::parse(&[1]));
+    assert_eq!(None, parse(1, SampleTp::U8 as u8, &[]));
+    assert_eq!(None, parse(1, SampleTp::U16 as u8, &[0x01, 0x02, 0x03]));
+    assert_eq!(None, parse(1, SampleTp::F32 as u8, &[0x3F, 0xC0, 0x00]));
+  }
+
+  #[test]
+  fn unknown_tp() {
+    assert_eq!(None, parse(1, 0, &[0x01]));
+    assert_eq!(None, parse(1, 6, &[0x01, 0x02, 0x03, 0x04]));
+  }
+}
@@ -0,0 +1,247 @@
+use crate::capture::{Dir, Port, Rec};
+use crate::protocol::{BlockMode, FrmDec, OpFlag};
+use crate::{f, mk_err_str, we, Rst, Str, FRM_HEAD_LEN, FRM_MIN_LEN, FRM_TAIL_LEN};
+use core::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use tracing::debug;
+
+pub const LINKTYPE_USER0: u16 = 147;
+
+const BLK_SHB: u32 = 0x0A0D_0D0A;
+const BLK_IDB: u32 = 0x0000_0001;
+const BLK_EPB: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const OPT_END: u16 = 0;
+const OPT_EPB_FLAGS: u16 = 2;
+const OPT_IF_NAME: u16 = 2;
+
+pub fn frames(recs: &[Rec]) -> Vec<Rec> {
+  let mut decs: [FrmDec; 4] = Default::default();
+  let mut frms = Vec::new();
+  for rec in recs {
+    let dec = &mut decs[2 * rec.port as usize + rec.dir as usize];
+    dec.push(&rec.data);
+    for frm in dec {
+      match frm {
+        Ok(data) => frms.push(Rec { data, ..rec.clone() }),
+        Err(e) => debug!(port = ?rec.port, dir = ?rec.dir, "{e}"),
+      }
+    }
+  }
+  frms
+}
+
+fn write_blk(w: &mut impl Write, tp: u32, body: &[u8]) -> io::Result<()> {
+  let pad = body.len().next_multiple_of(4) - body.len();
+  let total_len = (12 + body.len() + pad) as u32;
+  w.write_all(&tp.to_le_bytes())?;
+  w.write_all(&total_len.to_le_bytes())?;
+  w.write_all(body)?;
+  w.write_all(&[0; 3][..pad])?;
+  w.write_all(&total_len.to_le_bytes())
+}
+
+pub fn write_pcapng(w: &mut impl Write, frms: &[Rec]) -> io::Result<()> {
+  let mut shb = Vec::with_capacity(16);
+  shb.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+  shb.extend_from_slice(&1u16.to_le_bytes());
+  shb.extend_from_slice(&0u16.to_le_bytes());
+  shb.extend_from_slice(&(-1i64).to_le_bytes());
+  write_blk(w, BLK_SHB, &shb)?;
+
+  for port in [Port::Dev, Port::Peer] {
+    let name = port.name().as_bytes();
+    let mut idb = Vec::with_capacity(16);
+    idb.extend_from_slice(&LINKTYPE_USER0.to_le_bytes());
+    idb.extend_from_slice(&0u16.to_le_bytes());
+    idb.extend_from_slice(&0u32.to_le_bytes());
+    idb.extend_from_slice(&OPT_IF_NAME.to_le_bytes());
+    idb.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    idb.extend_from_slice(name);
+    idb.resize(idb.len().next_multiple_of(4), 0);
+    idb.extend_from_slice(&OPT_END.to_le_bytes());
+    idb.extend_from_slice(&0u16.to_le_bytes());
+    write_blk(w, BLK_IDB, &idb)?;
+  }
+
+  for frm in frms {
+    let us = frm.micros();
+    let len = frm.data.len() as u32;
+    let mut epb = Vec::with_capacity(20 + frm.data.len() + 16);
+    epb.extend_from_slice(&(frm.port as u32).to_le_bytes());
+    epb.extend_from_slice(&((us >> 32) as u32).to_le_bytes());
+    epb.extend_from_slice(&(us as u32).to_le_bytes());
+    epb.extend_from_slice(&len.to_le_bytes());
+    epb.extend_from_slice(&len.to_le_bytes());
+    epb.extend_from_slice(&frm.data);
+    epb.resize(epb.len().next_multiple_of(4), 0);
+    let flags: u32 = match frm.dir {
+      Dir::Rx => 1,
+      Dir::Tx => 2,
+    };
+    epb.extend_from_slice(&OPT_EPB_FLAGS.to_le_bytes());
+    epb.extend_from_slice(&4u16.to_le_bytes());
+    epb.extend_from_slice(&flags.to_le_bytes());
+    epb.extend_from_slice(&OPT_END.to_le_bytes());
+    epb.extend_from_slice(&0u16.to_le_bytes());
+    write_blk(w, BLK_EPB, &epb)?;
+  }
+  w.flush()
+}
+
+pub fn export(recs: &[Rec], path: &Path) -> Rst<usize> {
+  let frms = frames(recs);
+  let mut w = match File::create(path) {
+    Ok(file) => BufWriter::new(file),
+    Err(e) => {
+      we!("{}", mk_err_str(e, "pcapng 文件创建失败"));
+    }
+  };
+  if let Err(e) = write_pcapng(&mut w, &frms) {
+    we!("{}", mk_err_str(e, "pcapng 文件写入失败"));
+  }
+  Ok(frms.len())
+}
+
+pub fn dissector() -> Str {
+  let ops = OpFlag::ALL.iter().fold(Str::new(), |mut s, op| {
+    let _ = writeln!(s, "  [{}] = \"{op:?}\",", *op as u8);
+    s
+  });
+  let modes = BlockMode::ALL.iter().fold(Str::new(), |mut s, mode| {
+    let _ = writeln!(s, "  [{}] = \"{mode:?}\",", *mode as u8);
+    s
+  });
+  f!(
+    r#"-- uifs 帧解析器，由 uifs 根据 protocol.rs 中的定义生成，请勿手动修改
+-- 将本文件放入 Wireshark 的个人插件目录后，即可用 uifs.op == 2 等表达式过滤帧
+
+local uifs = Proto("uifs", "UIFS Frame")
+
+local ops = {{
+{ops}}}
+
+local modes = {{
+{modes}}}
+
+local HEAD_LEN = {FRM_HEAD_LEN}
+local TAIL_LEN = {FRM_TAIL_LEN}
+local MIN_LEN = {FRM_MIN_LEN}
+
+local f_start = ProtoField.uint8("uifs.start", "Start", base.HEX)
+local f_len = ProtoField.uint16("uifs.len", "Length", base.DEC)
+local f_op = ProtoField.uint8("uifs.op", "Opcode", base.DEC, ops)
+local f_mode = ProtoField.uint8("uifs.mode", "Mode", base.DEC, modes)
+local f_payload = ProtoField.bytes("uifs.payload", "Payload")
+local f_tail = ProtoField.uint16("uifs.tail", "Tail", base.HEX)
+
+uifs.fields = {{ f_start, f_len, f_op, f_mode, f_payload, f_tail }}
+
+function uifs.dissector(buf, pinfo, tree)
+  local len = buf:len()
+  if len < MIN_LEN then
+    return 0
+  end
+  pinfo.cols.protocol = "UIFS"
+  local t = tree:add(uifs, buf())
+  t:add(f_start, buf(0, 1))
+  t:add(f_len, buf(1, 2))
+  t:add(f_op, buf(3, 1))
+  t:add(f_mode, buf(4, 1))
+  if len > MIN_LEN then
+    t:add(f_payload, buf(HEAD_LEN, len - MIN_LEN))
+  end
+  t:add(f_tail, buf(len - TAIL_LEN, TAIL_LEN))
+  local op = buf(3, 1):uint()
+  pinfo.cols.info = (ops[op] or ("未知操作 " .. op)) .. "，" .. (len - MIN_LEN) .. " 字节负载"
+  return len
+end
+
+local encaps = wtap_encaps or wtap
+DissectorTable.get("wtap_encap"):add(encaps.USER0, uifs)
+"#
+  )
+}
+
+pub fn write_dissector(path: &Path) -> Rst<()> {
+  if let Err(e) = std::fs::write(path, dissector()) {
+    we!("{}", mk_err_str(e, "解析器文件写入失败"));
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::protocol;
+  use bytes::Bytes;
+  use core::time::Duration as Durn;
+  use std::time::UNIX_EPOCH;
+
+  fn rec(us: u64, port: Port, dir: Dir, data: &[u8]) -> Rec {
+    Rec { at: UNIX_EPOCH + Durn::from_micros(us), port, dir, data: Bytes::copy_from_slice(data) }
+  }
+
+  fn u32_at(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(buf[at..at + 4].try_into().unwrap())
+  }
+
+  fn blks(buf: &[u8]) -> Vec<(u32, &[u8])> {
+    let mut blks = Vec::new();
+    let mut at = 0;
+    while at < buf.len() {
+      let len = u32_at(buf, at + 4) as usize;
+      assert_eq!(0, len % 4);
+      assert_eq!(len as u32, u32_at(buf, at + len - 4));
+      blks.push((u32_at(buf, at), &buf[at + 8..at + len - 4]));
+      at += len;
+    }
+    assert_eq!(buf.len(), at);
+    blks
+  }
+
+  #[test]
+  fn frames_per_port_and_dir() {
+    let dev = protocol::sm3(b"abc");
+    let peer = protocol::sm4_enc_ecb(&[7; 16]);
+    let recs = [
+      rec(1, Port::Dev, Dir::Tx, &dev[..4]),
+      rec(2, Port::Peer, Dir::Tx, &peer[..6]),
+      rec(3, Port::Dev, Dir::Rx, b"junk"),
+      rec(4, Port::Peer, Dir::Tx, &peer[6..]),
+      rec(5, Port::Dev, Dir::Tx, &dev[4..]),
+    ];
+    let frms = frames(&recs);
+    assert_eq!(vec![rec(4, Port::Peer, Dir::Tx, &peer), rec(5, Port::Dev, Dir::Tx, &dev)], frms);
+  }
+
+  #[test]
+  fn block_layout() {
+    let frm = protocol::sm3(b"uifs!");
+    let frms = [rec(0x1_0000_0002, Port::Peer, Dir::Rx, &frm), rec(3, Port::Dev, Dir::Tx, &frm)];
+    let mut buf = Vec::new();
+    write_pcapng(&mut buf, &frms).unwrap();
+    let blks = blks(&buf);
+    let tps: Vec<_> = blks.iter().map(|&(tp, _)| tp).collect();
+    assert_eq!(vec![BLK_SHB, BLK_IDB, BLK_IDB, BLK_EPB, BLK_EPB], tps);
+    assert_eq!(BYTE_ORDER_MAGIC, u32_at(blks[0].1, 0));
+    for (port, (_, idb)) in [Port::Dev, Port::Peer].into_iter().zip(&blks[1..3]) {
+      assert_eq!(LINKTYPE_USER0.to_le_bytes(), idb[..2]);
+      assert_eq!(OPT_IF_NAME.to_le_bytes(), idb[8..10]);
+      assert_eq!(port.name().as_bytes(), &idb[12..12 + port.name().len()]);
+    }
+    for ((_, epb), (port, flags)) in blks[3..].iter().zip([(1, 1), (0, 2)]) {
+      assert_eq!(port, u32_at(epb, 0));
+      assert_eq!(frm.len() as u32, u32_at(epb, 12));
+      assert_eq!(frm.len() as u32, u32_at(epb, 16));
+      assert_eq!(&frm[..], &epb[20..20 + frm.len()]);
+      let opts = 20 + frm.len().next_multiple_of(4);
+      assert_eq!(OPT_EPB_FLAGS.to_le_bytes(), epb[opts..opts + 2]);
+      assert_eq!(flags, u32_at(epb, opts + 4));
+    }
+    assert_eq!((1, 2), (u32_at(blks[3].1, 4), u32_at(blks[3].1, 8)));
+    assert_eq!((0, 3), (u32_at(blks[4].1, 4), u32_at(blks[4].1, 8)));
+  }
+}
@@ -0,0 +1,111 @@
+use crate::protocol::OpFlag;
+use crate::{Dbg, Opt};
+use bytes::Bytes;
+use core::time::Duration as Durn;
+use std::{collections::VecDeque, sync::Mutex, time::Instant};
+
+#[derive(Dbg)]
+struct Req {
+  op: OpFlag,
+  frm: Bytes,
+  first: Instant,
+  sent: Instant,
+  timeout: Durn,
+  tries: u32,
+  retries: u32,
+}
+
+#[derive(Dbg)]
+pub enum Act {
+  Resend(OpFlag, Bytes, u32),
+  Timeout(OpFlag, Durn),
+}
+
+#[derive(Dbg, Default)]
+pub struct Pending {
+  reqs: Mutex<VecDeque<Req>>,
+}
+
+impl Pending {
+  pub const fn new() -> Self {
+    Self { reqs: Mutex::new(VecDeque::new()) }
+  }
+
+  pub fn track(&self, op: OpFlag, frm: Bytes, timeout: Durn, retries: u32) {
+    let now = Instant::now();
+    let retries = if op.idempotent() { retries } else { 0 };
+    let req = Req { op, frm, first: now, sent: now, timeout, tries: 0, retries };
+    self.reqs.lock().unwrap().push_back(req);
+  }
+
+  pub fn sent(&self, op: OpFlag, now: Instant) {
+    let mut reqs = self.reqs.lock().unwrap();
+    if let Some(req) = reqs.iter_mut().rev().find(|req| op == req.op) {
+      req.sent = now;
+    }
+  }
+
+  pub fn untrack(&self, op: OpFlag) {
+    let mut reqs = self.reqs.lock().unwrap();
+    if let Some(i) = reqs.iter().rposition(|req| op == req.op) {
+      reqs.remove(i);
+    }
+  }
+
+  pub fn clear(&self) {
+    self.reqs.lock().unwrap().clear();
+  }
+
+  pub fn poll(&self, now: Instant) -> Vec<Act> {
+    let mut acts = Vec::new();
+    self.reqs.lock().unwrap().retain_mut(|req| {
+      if now.saturating_duration_since(req.sent) < req.timeout {
+        return true;
+      }
+      if req.tries < req.retries {
+        req.tries += 1;
+        req.sent = now;
+        acts.push(Act::Resend(req.op, req.frm.clone(), req.tries));
+        return true;
+      }
+      acts.push(Act::Timeout(req.op, now.saturating_duration_since(req.first)));
+      false
+    });
+    acts
+  }
+
+  pub fn complete(&self, op: OpFlag, now: Instant) -> Opt<Durn> {
+    let mut reqs = self.reqs.lock().unwrap();
+    let i = reqs.iter().position(|req| op == req.op)?;
+    reqs.remove(i).map(|req| now.saturating_duration_since(req.sent))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn rtt_from_sent() {
+    let pending = Pending::new();
+    pending.track(OpFlag::Sm3, Bytes::new(), Durn::from_secs(1), 0);
+    let sent = Instant::now() + Durn::from_millis(50);
+    pending.sent(OpFlag::Sm3, sent);
+    assert_eq!(None, pending.complete(OpFlag::Key, sent));
+    let rtt = pending.complete(OpFlag::Sm3, sent + Durn::from_millis(5));
+    assert_eq!(Some(Durn::from_millis(5)), rtt);
+    assert_eq!(None, pending.complete(OpFlag::Sm3, sent));
+  }
+
+  #[test]
+  fn untrack_latest() {
+    let pending = Pending::new();
+    pending.track(OpFlag::Sm3, Bytes::new(), Durn::from_secs(1), 0);
+    let first = Instant::now();
+    pending.sent(OpFlag::Sm3, first);
+    pending.track(OpFlag::Sm3, Bytes::new(), Durn::from_secs(1), 0);
+    pending.untrack(OpFlag::Sm3);
+    let rtt = pending.complete(OpFlag::Sm3, first + Durn::from_millis(3));
+    assert_eq!(Some(Durn::from_millis(3)), rtt);
+  }
+}
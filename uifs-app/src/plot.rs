@@ -0,0 +1,107 @@
+use crate::{append_dp_text, inspector, AppWindow, Plot, TraceItem, WEAK_APP};
+use core::cell::RefCell;
+use slint::{ComponentHandle, ModelRc};
+use std::fs::File;
+use std::io::BufWriter;
+use std::time::SystemTime;
+use tracing::{info, warn};
+use uifs_app::obsr::{Obsr, Traces};
+use uifs_app::vcd::write_vcd;
+use uifs_app::{mk_err_str, slint_f};
+
+thread_local! {
+  static TRACES: RefCell<Traces> = const { RefCell::new(Traces::new()) };
+  static START: SystemTime = SystemTime::now();
+}
+
+pub fn secs_since_start(at: SystemTime) -> f64 {
+  START.with(|start| at.duration_since(*start).unwrap_or_default().as_secs_f64())
+}
+
+fn now_s() -> f64 {
+  secs_since_start(SystemTime::now())
+}
+
+pub fn push(obsr: Obsr) {
+  let t = now_s();
+  TRACES.with_borrow_mut(|traces| traces.push(t, &obsr));
+}
+
+pub fn render() {
+  let app = WEAK_APP.with(|w| w.get().unwrap().unwrap());
+  let plot = app.global::<Plot>();
+  if plot.get_paused() {
+    return;
+  }
+  let window = plot.get_window_s().max(1) as f64;
+  let t_end = now_s();
+  TRACES.with_borrow(|traces| {
+    let Some(range) = traces.y_range(t_end - window) else {
+      plot.set_traces(ModelRc::default());
+      plot.set_y_min(Default::default());
+      plot.set_y_max(Default::default());
+      return;
+    };
+    let items: Vec<_> = traces
+      .chans()
+      .map(|(ch, trace)| TraceItem {
+        name: slint_f!("通道 {ch}（{}）", trace.tp.name()),
+        commands: Traces::path(trace, t_end, window, range).into(),
+        kind: ch as i32,
+      })
+      .collect();
+    plot.set_traces(ModelRc::from(items.as_slice()));
+    plot.set_y_min(slint_f!("{:.3}", range.0));
+    plot.set_y_max(slint_f!("{:.3}", range.1));
+  });
+}
+
+fn export_csv(path: &str) {
+  let written = File::create(path)
+    .and_then(|file| TRACES.with_borrow(|traces| traces.write_csv(&mut BufWriter::new(file))));
+  match written {
+    Ok(()) => {
+      info!(path = path, "观测数据已导出");
+      append_dp_text(slint_f!("观测数据已导出：{path}"));
+    }
+    Err(e) => {
+      let e = mk_err_str(e, "观测数据导出失败");
+      warn!(path = path, "{e}");
+      append_dp_text(slint_f!("{e}"));
+    }
+  }
+}
+
+fn export_vcd(path: &str, with_frms: bool) {
+  let frms: Vec<_> = if with_frms {
+    let frms = inspector::frames().into_iter();
+    frms.map(|(at, dir, op)| (secs_since_start(at), dir, op)).collect()
+  } else {
+    Vec::new()
+  };
+  let written = File::create(path).and_then(|file| {
+    TRACES.with_borrow(|traces| write_vcd(&mut BufWriter::new(file), traces, &frms))
+  });
+  match written {
+    Ok(()) => {
+      info!(path = path, "VCD 已导出");
+      append_dp_text(slint_f!("VCD 已导出：{path}"));
+    }
+    Err(e) => {
+      let e = mk_err_str(e, "VCD 导出失败");
+      warn!(path = path, "{e}");
+      append_dp_text(slint_f!("{e}"));
+    }
+  }
+}
+
+pub fn init(app: &AppWindow) {
+  START.with(|_| ());
+  let plot = app.global::<Plot>();
+  plot.on_export_csv(|path| export_csv(path.as_str()));
+  plot.on_export_vcd(|path, with_frms| export_vcd(path.as_str(), with_frms));
+  plot.on_clear(|| {
+    TRACES.with_borrow_mut(Traces::clear);
+    render();
+  });
+}
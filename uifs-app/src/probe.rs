@@ -0,0 +1,55 @@
+use crate::conf::SpConf;
+use crate::protocol::{sm3, FrmDec, Rsp};
+use crate::{Opt, Str, SM3_HASH_LEN};
+use core::time::Duration as Durn;
+use serialport::ClearBuffer;
+use std::io::ErrorKind;
+use std::time::Instant;
+use tracing::debug;
+
+pub const PROBE_BAUD_RATES: [u32; 8] =
+  [115_200, 9_600, 19_200, 38_400, 57_600, 230_400, 460_800, 921_600];
+pub const PROBE_MSG: &[u8] = b"abc";
+pub const PROBE_HASH: [u8; SM3_HASH_LEN] = [
+  0x66, 0xc7, 0xf0, 0xf4, 0x62, 0xee, 0xed, 0xd9, 0xd1, 0xf2, 0xd4, 0x6b, 0xdc, 0x10, 0xe4, 0xe2,
+  0x41, 0x67, 0xc4, 0x87, 0x5c, 0xf2, 0xf7, 0xa2, 0x29, 0x7d, 0xa0, 0x2b, 0x8f, 0x4b, 0xa8, 0xe0,
+];
+pub const PROBE_WAIT: Durn = Durn::from_millis(300);
+
+pub fn probe(path: &str, sp_conf: &SpConf) -> bool {
+  let sp_conf = SpConf { timeout_ms: 20, ..sp_conf.clone() };
+  let mut sp = match sp_conf.builder(path).open() {
+    Ok(sp) => sp,
+    Err(e) => {
+      debug!(path = path, e = ?e, "探测时无法打开串口");
+      return false;
+    }
+  };
+  let _ = sp.clear(ClearBuffer::All);
+  if sp.write_all(&sm3(PROBE_MSG)).is_err() {
+    return false;
+  }
+  let mut dec = FrmDec::default();
+  let mut buf = [0u8; 256];
+  let deadline = Instant::now() + PROBE_WAIT;
+  while Instant::now() < deadline {
+    match sp.read(&mut buf) {
+      Ok(n) => dec.push(&buf[..n]),
+      Err(e) if ErrorKind::TimedOut == e.kind() => continue,
+      Err(_) => return false,
+    }
+    if (&mut dec).flatten().any(|frm| Ok(Rsp::Sm3(PROBE_HASH)) == Rsp::parse(&frm)) {
+      return true;
+    }
+  }
+  false
+}
+
+pub fn find(paths: &[Str], sp_conf: &SpConf) -> Opt<(Str, u32)> {
+  paths.iter().find_map(|path| {
+    PROBE_BAUD_RATES.into_iter().find_map(|baud_rate| {
+      debug!(path = path, baud_rate = baud_rate, "探测设备");
+      probe(path, &SpConf { baud_rate, ..sp_conf.clone() }).then(|| (path.clone(), baud_rate))
+    })
+  })
+}
@@ -1,7 +1,7 @@
+use crate::*;
 use bytes::{BufMut, Bytes, BytesMut};
-use uifs_app::*;
 
-#[derive(num_enum::TryFromPrimitive)]
+#[derive(Clone, Copy, Dbg, PartialEq, num_enum::TryFromPrimitive)]
 #[repr(u8)]
 pub enum OpFlag {
   Key = 1,
@@ -9,12 +9,23 @@ pub enum OpFlag {
   Sm4Enc = 3,
   Sm4Dec = 4,
 }
-#[derive(num_enum::TryFromPrimitive, PartialEq)]
+impl OpFlag {
+  pub const ALL: [Self; 4] = [Self::Key, Self::Sm3, Self::Sm4Enc, Self::Sm4Dec];
+
+  pub const fn idempotent(self) -> bool {
+    !matches!(self, Self::Key)
+  }
+}
+
+#[derive(Clone, Copy, Dbg, PartialEq, num_enum::TryFromPrimitive)]
 #[repr(u8)]
 pub enum BlockMode {
   ECB = 1,
   CBC = 2,
 }
+impl BlockMode {
+  pub const ALL: [Self; 2] = [Self::ECB, Self::CBC];
+}
 
 pub fn key(k: &[u8; 16]) -> Bytes {
   const FRM_LEN: usize = FRM_HEAD_LEN + KEY_LEN + FRM_TAIL_LEN;
@@ -89,3 +100,195 @@ pub fn sm4_dec_ecb(ct: &[u8]) -> Bytes {
   buf.put_u16(FRM_PAR_FLAG);
   buf.freeze()
 }
+
+#[derive(Clone, Dbg, PartialEq, snafu::Snafu)]
+pub enum FrmErr {
+  #[snafu(display("帧长度有误：{len}"))]
+  Len { len: usize },
+  #[snafu(display("未知操作码：{op}"))]
+  Op { op: u8 },
+  #[snafu(display("未知分组模式：{mode}"))]
+  Mode { mode: u8 },
+  #[snafu(display("意外标识：{flag}"))]
+  Flag { flag: u8 },
+  #[snafu(display("校验位有误：{par:#06x}"))]
+  Par { par: u16 },
+}
+
+#[derive(Clone, Dbg, PartialEq)]
+pub enum Rsp {
+  Key,
+  Sm3([u8; SM3_HASH_LEN]),
+  Sm4Enc(BlockMode, Bytes),
+  Sm4Dec(BlockMode, Bytes),
+}
+
+impl Rsp {
+  pub const fn op(&self) -> OpFlag {
+    match self {
+      Self::Key => OpFlag::Key,
+      Self::Sm3(_) => OpFlag::Sm3,
+      Self::Sm4Enc(..) => OpFlag::Sm4Enc,
+      Self::Sm4Dec(..) => OpFlag::Sm4Dec,
+    }
+  }
+
+  pub fn parse(frm: &Bytes) -> Rst<Self, FrmErr> {
+    let len = frm.len();
+    if len < FRM_MIN_LEN {
+      return Err(FrmErr::Len { len });
+    }
+    let par = u16::from_be_bytes([frm[len - 2], frm[len - 1]]);
+    if FRM_PAR_FLAG != par {
+      return Err(FrmErr::Par { par });
+    }
+    let payload = frm.slice(FRM_HEAD_LEN..len - FRM_TAIL_LEN);
+    let op = OpFlag::try_from(frm[3]).map_err(|_| FrmErr::Op { op: frm[3] })?;
+    let mode = || BlockMode::try_from(frm[4]).map_err(|_| FrmErr::Mode { mode: frm[4] });
+    match op {
+      OpFlag::Key => {
+        if FRM_HEAD_LEN + 1 + FRM_TAIL_LEN != len {
+          return Err(FrmErr::Len { len });
+        }
+        if 0x01 != payload[0] {
+          return Err(FrmErr::Flag { flag: payload[0] });
+        }
+        Ok(Self::Key)
+      }
+      OpFlag::Sm3 => match payload.as_ref().try_into() {
+        Ok(hash) => Ok(Self::Sm3(hash)),
+        Err(_) => Err(FrmErr::Len { len }),
+      },
+      OpFlag::Sm4Enc => Ok(Self::Sm4Enc(mode()?, payload)),
+      OpFlag::Sm4Dec => Ok(Self::Sm4Dec(mode()?, payload)),
+    }
+  }
+}
+
+#[derive(Dbg, Default)]
+pub struct FrmDec {
+  buf: BytesMut,
+  skipped: usize,
+}
+
+impl FrmDec {
+  pub fn push(&mut self, data: &[u8]) {
+    self.buf.extend_from_slice(data);
+  }
+
+  pub fn clear(&mut self) {
+    self.buf.clear();
+  }
+
+  pub fn take_skipped(&mut self) -> usize {
+    core::mem::take(&mut self.skipped)
+  }
+}
+
+impl Iterator for FrmDec {
+  type Item = Rst<Bytes, FrmErr>;
+
+  fn next(&mut self) -> Opt<Self::Item> {
+    let start = self.buf.iter().position(|&b| FRM_START_FLAG == b).unwrap_or(self.buf.len());
+    let _ = self.buf.split_to(start);
+    self.skipped += start;
+    if self.buf.len() < FRM_HEAD_LEN {
+      return None;
+    }
+    let len = u16::from_be_bytes([self.buf[1], self.buf[2]]) as usize;
+    if !(FRM_MIN_LEN..=FRM_MAX_LEN).contains(&len) {
+      let _ = self.buf.split_to(1);
+      self.skipped += 1;
+      return Some(Err(FrmErr::Len { len }));
+    }
+    if len > self.buf.len() {
+      return None;
+    }
+    Some(Ok(self.buf.split_to(len).freeze()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rsp(op: u8, mode: u8, payload: &[u8]) -> Bytes {
+    let frm_len = FRM_HEAD_LEN + payload.len() + FRM_TAIL_LEN;
+    let mut buf = BytesMut::with_capacity(frm_len);
+    buf.put_u8(FRM_START_FLAG);
+    buf.put_u16(frm_len as u16);
+    buf.put_u8(op);
+    buf.put_u8(mode);
+    buf.put_slice(payload);
+    buf.put_u16(FRM_PAR_FLAG);
+    buf.freeze()
+  }
+
+  fn drain(dec: &mut FrmDec) -> Vec<Rst<Bytes, FrmErr>> {
+    dec.by_ref().collect()
+  }
+
+  #[test]
+  fn dec_split_frame() {
+    let frm = rsp(OpFlag::Sm3 as u8, 0, &[0xAB; SM3_HASH_LEN]);
+    let mut dec = FrmDec::default();
+    for chunk in frm.chunks(3) {
+      assert!(drain(&mut dec).is_empty());
+      dec.push(chunk);
+    }
+    assert_eq!(vec![Ok(frm)], drain(&mut dec));
+    assert_eq!(0, dec.take_skipped());
+  }
+
+  #[test]
+  fn dec_concatenated_frames() {
+    let key = rsp(OpFlag::Key as u8, 0, &[0x01]);
+    let ct = rsp(OpFlag::Sm4Enc as u8, BlockMode::ECB as u8, &[0x11; 16]);
+    let mut dec = FrmDec::default();
+    dec.push(&[key.as_ref(), ct.as_ref()].concat());
+    assert_eq!(vec![Ok(key), Ok(ct)], drain(&mut dec));
+  }
+
+  #[test]
+  fn dec_skips_garbage() {
+    let frm = rsp(OpFlag::Key as u8, 0, &[0x01]);
+    let mut dec = FrmDec::default();
+    dec.push(b"noise");
+    dec.push(&frm);
+    assert_eq!(vec![Ok(frm)], drain(&mut dec));
+    assert_eq!(5, dec.take_skipped());
+    assert_eq!(0, dec.take_skipped());
+  }
+
+  #[test]
+  fn dec_bad_len_resyncs() {
+    let frm = rsp(OpFlag::Key as u8, 0, &[0x01]);
+    let mut dec = FrmDec::default();
+    dec.push(&[FRM_START_FLAG, 0x00, 0x02, 0x01, 0x00]);
+    dec.push(&frm);
+    assert_eq!(vec![Err(FrmErr::Len { len: 2 }), Ok(frm)], drain(&mut dec));
+    assert_eq!(5, dec.take_skipped());
+  }
+
+  #[test]
+  fn parse_rsps() {
+    assert_eq!(Ok(Rsp::Key), Rsp::parse(&rsp(1, 0, &[0x01])));
+    assert_eq!(Ok(Rsp::Sm3([7; SM3_HASH_LEN])), Rsp::parse(&rsp(2, 0, &[7; SM3_HASH_LEN])));
+    let data = Bytes::from_static(&[9; 16]);
+    assert_eq!(Ok(Rsp::Sm4Enc(BlockMode::CBC, data.clone())), Rsp::parse(&rsp(3, 2, &data)));
+    assert_eq!(Ok(Rsp::Sm4Dec(BlockMode::ECB, data.clone())), Rsp::parse(&rsp(4, 1, &data)));
+  }
+
+  #[test]
+  fn parse_errs() {
+    assert_eq!(Err(FrmErr::Len { len: 3 }), Rsp::parse(&Bytes::from_static(&[0xC0, 0, 3])));
+    assert_eq!(Err(FrmErr::Len { len: 9 }), Rsp::parse(&rsp(1, 0, &[0x01, 0x01])));
+    assert_eq!(Err(FrmErr::Len { len: 10 }), Rsp::parse(&rsp(2, 0, &[0; 3])));
+    assert_eq!(Err(FrmErr::Op { op: 9 }), Rsp::parse(&rsp(9, 0, &[])));
+    assert_eq!(Err(FrmErr::Mode { mode: 7 }), Rsp::parse(&rsp(3, 7, &[0; 16])));
+    assert_eq!(Err(FrmErr::Flag { flag: 0 }), Rsp::parse(&rsp(1, 0, &[0x00])));
+    let mut frm = rsp(1, 0, &[0x01]).to_vec();
+    frm[7] = 0x5A;
+    assert_eq!(Err(FrmErr::Par { par: 0x5A }), Rsp::parse(&Bytes::from(frm)));
+  }
+}
@@ -0,0 +1,102 @@
+use crate::bench::with_timeout;
+use crate::capture::{Dir, Port, Rec};
+use crate::event::{Evt, Sink};
+use crate::protocol::{FrmDec, Rsp};
+use crate::{mk_err_str, we, Dbg, Rst};
+use bytes::{Bytes, BytesMut};
+use core::time::Duration as Durn;
+use serialport::{ClearBuffer, SerialPort};
+use std::io::ErrorKind;
+use std::time::Instant;
+use tracing::debug;
+
+pub const REPLAY_WAIT: Durn = Durn::from_millis(500);
+
+#[derive(Clone, Dbg, Default)]
+pub struct Xchg {
+  pub tx: Bytes,
+  pub rx: Bytes,
+}
+
+#[derive(Clone, Dbg)]
+pub struct Diff {
+  pub idx: usize,
+  pub tx: Bytes,
+  pub expected: Bytes,
+  pub got: Bytes,
+}
+
+impl Diff {
+  pub fn offset(&self) -> usize {
+    self.expected.iter().zip(&self.got).take_while(|(e, g)| e == g).count()
+  }
+}
+
+pub fn xchgs(recs: &[Rec]) -> Vec<Xchg> {
+  let mut xchgs = Vec::new();
+  let (mut tx, mut rx) = (BytesMut::new(), BytesMut::new());
+  for rec in recs.iter().filter(|rec| Port::Dev == rec.port) {
+    match rec.dir {
+      Dir::Tx if !rx.is_empty() => {
+        xchgs.push(Xchg { tx: tx.split().freeze(), rx: rx.split().freeze() });
+        tx.extend_from_slice(&rec.data);
+      }
+      Dir::Tx => tx.extend_from_slice(&rec.data),
+      Dir::Rx if !tx.is_empty() => rx.extend_from_slice(&rec.data),
+      Dir::Rx => debug!(data = const_hex::encode(&rec.data), "忽略首次发送前收到的数据"),
+    }
+  }
+  if !tx.is_empty() {
+    xchgs.push(Xchg { tx: tx.freeze(), rx: rx.freeze() });
+  }
+  xchgs
+}
+
+pub fn decode(recs: &[Rec], sink: &dyn Sink) {
+  let mut dec = FrmDec::default();
+  for rec in recs.iter().filter(|rec| Port::Dev == rec.port && Dir::Rx == rec.dir) {
+    dec.push(&rec.data);
+    for frm in &mut dec {
+      let evt = match frm.and_then(|frm| Rsp::parse(&frm)) {
+        Ok(rsp) => Evt::Rsp(rsp),
+        Err(e) => Evt::FrmErr(e),
+      };
+      sink.emit(&evt);
+    }
+  }
+}
+
+fn read_rsp(sp: &mut dyn SerialPort, len: usize, wait: Durn) -> Rst<Bytes> {
+  let mut got = BytesMut::new();
+  let mut buf = [0u8; 256];
+  let deadline = Instant::now() + wait;
+  while Instant::now() < deadline && (0 == len || got.len() < len) {
+    match sp.read(&mut buf) {
+      Ok(n) => got.extend_from_slice(&buf[..n]),
+      Err(e) if ErrorKind::TimedOut == e.kind() => continue,
+      Err(e) => {
+        we!("{}", mk_err_str(e, "读取串口数据失败"));
+      }
+    }
+  }
+  Ok(got.freeze())
+}
+
+pub fn run(sp: &mut dyn SerialPort, xchgs: &[Xchg], wait: Durn) -> Rst<Vec<Diff>> {
+  with_timeout(sp, Durn::from_millis(20), |sp| compare(sp, xchgs, wait))
+}
+
+fn compare(sp: &mut dyn SerialPort, xchgs: &[Xchg], wait: Durn) -> Rst<Vec<Diff>> {
+  let mut diffs = Vec::new();
+  for (idx, xchg) in xchgs.iter().enumerate() {
+    let _ = sp.clear(ClearBuffer::Input);
+    if let Err(e) = sp.write_all(&xchg.tx) {
+      we!("{}", mk_err_str(e, "回放数据发送失败"));
+    }
+    let got = read_rsp(sp, xchg.rx.len(), wait)?;
+    if got != xchg.rx {
+      diffs.push(Diff { idx, tx: xchg.tx.clone(), expected: xchg.rx.clone(), got });
+    }
+  }
+  Ok(diffs)
+}
@@ -0,0 +1,147 @@
+use crate::{status, AppWindow, Options, Settings, ALL_SPS, CUR_MODE, WEAK_APP};
+use core::cell::{Cell, RefCell};
+use core::sync::atomic::Ordering::Relaxed;
+use slint::ComponentHandle;
+use tracing::{info, warn};
+use uifs_app::conf::{self, AppConf, DIS_LOG_FILE_ENV, ENBL_LOG_CNSL_ENV, LOG_DIR_ENV};
+use uifs_app::{slint_f, Opt, SlintStr, Str};
+
+thread_local! {
+  static CONF: RefCell<AppConf> = RefCell::new(AppConf::default());
+  static LOAD_FAILED: Cell<bool> = const { Cell::new(false) };
+}
+
+fn env_note(env: &str, value: &str) -> SlintStr {
+  if std::env::var_os(env).is_some() {
+    slint_f!("当前被环境变量 {env} 覆盖为：{value}")
+  } else {
+    SlintStr::new()
+  }
+}
+
+fn on_off(on: bool) -> &'static str {
+  if on {
+    "开启"
+  } else {
+    "关闭"
+  }
+}
+
+fn to_ui(settings: &Settings<'_>, conf: &AppConf) {
+  settings.set_log_dir(conf.log_dir.as_str().into());
+  settings.set_log_to_file(conf.log_to_file);
+  settings.set_log_to_cnsl(conf.log_to_cnsl);
+  settings.set_save_session(conf.save_session);
+}
+
+fn from_ui(settings: &Settings<'_>, conf: &mut AppConf) {
+  conf.log_dir = settings.get_log_dir().into();
+  conf.log_to_file = settings.get_log_to_file();
+  conf.log_to_cnsl = settings.get_log_to_cnsl();
+  conf.save_session = settings.get_save_session();
+}
+
+fn session_to_ui(opts: &Options<'_>, conf: &AppConf) {
+  let op = conf.op.clamp(0, 5);
+  opts.set_op(op);
+  opts.set_mode(conf.mode.clamp(0, 1));
+  opts.set_msg_enc(conf.msg_enc.clamp(0, 3));
+  opts.set_key_enc(conf.key_enc.clamp(0, 3));
+  opts.set_iv_enc(conf.iv_enc.clamp(0, 3));
+  opts.set_result_enc(conf.result_enc.clamp(0, 4));
+  opts.set_timeout_ms(conf.timeout_ms.max(0));
+  opts.set_retries(conf.retries.max(0));
+  opts.set_auto_reconnect(conf.auto_reconnect);
+  opts.set_chat(4 == op);
+  CUR_MODE.store(
+    match op {
+      0 => 0,
+      5 => 2,
+      _ => 1,
+    },
+    Relaxed,
+  );
+  opts.set_ks_key(conf.key_name.as_deref().unwrap_or_default().into());
+  let sp_idx = ALL_SPS.with_borrow(|sps| {
+    let name = conf.sp_name.as_ref()?;
+    sps.iter().position(|(sp, _)| &sp.port_name == name)
+  });
+  if let Some(idx) = sp_idx {
+    opts.set_sp_idx(idx as i32);
+  }
+}
+
+fn session_from_ui(opts: &Options<'_>, conf: &mut AppConf) {
+  conf.sp_name = ALL_SPS
+    .with_borrow(|sps| sps.get(opts.get_sp_idx() as usize).map(|(sp, _)| sp.port_name.clone()));
+  let key_name = opts.get_ks_key();
+  conf.key_name = (!key_name.is_empty()).then(|| key_name.into());
+  conf.op = opts.get_op();
+  conf.mode = opts.get_mode();
+  conf.msg_enc = opts.get_msg_enc();
+  conf.key_enc = opts.get_key_enc();
+  conf.iv_enc = opts.get_iv_enc();
+  conf.result_enc = opts.get_result_enc();
+  conf.timeout_ms = opts.get_timeout_ms();
+  conf.retries = opts.get_retries();
+  conf.auto_reconnect = opts.get_auto_reconnect();
+}
+
+pub fn init(app: &AppWindow, conf: AppConf, load_err: Opt<Str>) {
+  let settings = app.global::<Settings>();
+  let path = conf::conf_dir().map(|dir| dir.join(AppConf::FILE));
+  settings.set_path(path.map_or(SlintStr::from("（无法确定配置目录）"), |path| {
+    slint_f!("{}", path.display())
+  }));
+  let eff = conf.with_env();
+  settings.set_log_dir_env(env_note(LOG_DIR_ENV, &eff.log_dir));
+  settings.set_log_to_file_env(env_note(DIS_LOG_FILE_ENV, on_off(eff.log_to_file)));
+  settings.set_log_to_cnsl_env(env_note(ENBL_LOG_CNSL_ENV, on_off(eff.log_to_cnsl)));
+  to_ui(&settings, &conf);
+  session_to_ui(&app.global::<Options>(), &conf);
+  CONF.set(conf);
+  if let Some(e) = load_err {
+    LOAD_FAILED.set(true);
+    status(slint_f!("设置文件无效，已使用默认设置：{e}"), true);
+  }
+
+  settings.on_save(|| {
+    let app = WEAK_APP.with(|w| w.get().unwrap().unwrap());
+    let rst = CONF.with_borrow_mut(|conf| {
+      from_ui(&app.global::<Settings>(), conf);
+      if conf.save_session {
+        session_from_ui(&app.global::<Options>(), conf);
+      }
+      conf::save(AppConf::FILE, conf)
+    });
+    match rst {
+      Ok(()) => {
+        LOAD_FAILED.set(false);
+        info!("设置已保存");
+        status(slint_f!("设置已保存，日志设置重启后生效"), false);
+      }
+      Err(e) => {
+        warn!("{e}");
+        status(slint_f!("{e}"), true);
+      }
+    }
+  });
+
+  settings.on_reset(|| {
+    let app = WEAK_APP.with(|w| w.get().unwrap().unwrap());
+    to_ui(&app.global::<Settings>(), &AppConf::default());
+    status(slint_f!("已恢复默认设置，保存后生效"), false);
+  });
+}
+
+pub fn save_session(app: &AppWindow) {
+  CONF.with_borrow_mut(|conf| {
+    if !conf.save_session || LOAD_FAILED.get() {
+      return;
+    }
+    session_from_ui(&app.global::<Options>(), conf);
+    if let Err(e) = conf::save(AppConf::FILE, conf) {
+      warn!("{e}");
+    }
+  });
+}
@@ -0,0 +1,47 @@
+use crate::protocol::BlockMode;
+use crate::{mk_err_str, we, Rst, IV_LEN, KEY_LEN, SM3_HASH_LEN};
+use sm4::cipher::{generic_array::GenericArray, BlockDecrypt, BlockEncrypt, KeyInit};
+
+pub fn sm3(m: &[u8]) -> [u8; SM3_HASH_LEN] {
+  use sm3::Digest;
+  sm3::Sm3::digest(m).into()
+}
+
+pub fn random<const N: usize>() -> Rst<[u8; N]> {
+  let mut buf = [0; N];
+  if let Err(e) = getrandom::getrandom(&mut buf) {
+    we!("{}", mk_err_str(e, "系统随机数获取失败"));
+  }
+  Ok(buf)
+}
+
+pub fn sm4(
+  key: &[u8; KEY_LEN],
+  enc: bool,
+  mode: BlockMode,
+  iv: &[u8; IV_LEN],
+  data: &[u8],
+) -> Vec<u8> {
+  let cipher = sm4::Sm4::new(GenericArray::from_slice(key));
+  let mut out = data.to_vec();
+  out.resize(data.len().div_ceil(16) * 16, 0);
+  let mut chain = *iv;
+  for blk in out.chunks_exact_mut(16) {
+    match (enc, mode) {
+      (true, BlockMode::ECB) => cipher.encrypt_block(GenericArray::from_mut_slice(blk)),
+      (false, BlockMode::ECB) => cipher.decrypt_block(GenericArray::from_mut_slice(blk)),
+      (true, BlockMode::CBC) => {
+        blk.iter_mut().zip(chain).for_each(|(b, c)| *b ^= c);
+        cipher.encrypt_block(GenericArray::from_mut_slice(blk));
+        chain.copy_from_slice(blk);
+      }
+      (false, BlockMode::CBC) => {
+        let next: [u8; 16] = (&*blk).try_into().unwrap();
+        cipher.decrypt_block(GenericArray::from_mut_slice(blk));
+        blk.iter_mut().zip(chain).for_each(|(b, c)| *b ^= c);
+        chain = next;
+      }
+    }
+  }
+  out
+}
@@ -0,0 +1,87 @@
+use crate::event::{Evt, Sink};
+use crate::protocol::FrmErr;
+use crate::{Dbg, Opt};
+use std::sync::Mutex;
+use std::time::Instant;
+
+#[derive(Clone, Copy, Dbg, Default, PartialEq)]
+pub struct Counts {
+  pub tx_bytes: u64,
+  pub rx_bytes: u64,
+  pub frms: u64,
+  pub skipped: u64,
+  pub len_errs: u64,
+  pub op_errs: u64,
+  pub par_errs: u64,
+  pub other_errs: u64,
+  pub timeouts: u64,
+}
+
+impl Counts {
+  pub const fn total_bytes(&self) -> u64 {
+    self.tx_bytes + self.rx_bytes
+  }
+}
+
+#[derive(Default)]
+pub struct Stats {
+  counts: Mutex<Counts>,
+  last: Mutex<Opt<(Instant, u64)>>,
+}
+
+impl Stats {
+  pub const fn new() -> Self {
+    Self {
+      counts: Mutex::new(Counts {
+        tx_bytes: 0,
+        rx_bytes: 0,
+        frms: 0,
+        skipped: 0,
+        len_errs: 0,
+        op_errs: 0,
+        par_errs: 0,
+        other_errs: 0,
+        timeouts: 0,
+      }),
+      last: Mutex::new(None),
+    }
+  }
+
+  pub fn snapshot(&self) -> Counts {
+    *self.counts.lock().unwrap()
+  }
+
+  pub fn reset(&self) {
+    *self.counts.lock().unwrap() = Counts::default();
+    self.last.lock().unwrap().take();
+  }
+
+  pub fn throughput(&self, now: Instant) -> f64 {
+    let total = self.snapshot().total_bytes();
+    let mut last = self.last.lock().unwrap();
+    let rate = match *last {
+      Some((at, prev)) if now > at => total.saturating_sub(prev) as f64 / (now - at).as_secs_f64(),
+      _ => 0.0,
+    };
+    *last = Some((now, total));
+    rate
+  }
+}
+
+impl Sink for Stats {
+  fn emit(&self, evt: &Evt) {
+    let mut counts = self.counts.lock().unwrap();
+    match evt {
+      Evt::Tx(data) => counts.tx_bytes += data.len() as u64,
+      Evt::Rx(data) => counts.rx_bytes += data.len() as u64,
+      Evt::Skipped(n) => counts.skipped += *n as u64,
+      Evt::Rsp(_) | Evt::Obsr(_) => counts.frms += 1,
+      Evt::FrmErr(FrmErr::Len { .. }) => counts.len_errs += 1,
+      Evt::FrmErr(FrmErr::Op { .. }) => counts.op_errs += 1,
+      Evt::FrmErr(FrmErr::Par { .. }) => counts.par_errs += 1,
+      Evt::FrmErr(_) => counts.other_errs += 1,
+      Evt::Timeout { .. } => counts.timeouts += 1,
+      _ => {}
+    }
+  }
+}
@@ -0,0 +1,106 @@
+use crate::{Dbg, Str};
+use vt100::{Cell, Color, Parser};
+
+pub const TERM_ROWS: u16 = 24;
+pub const TERM_COLS: u16 = 80;
+pub const TERM_FG: [u8; 3] = [0xd0, 0xd0, 0xd0];
+pub const TERM_BG: [u8; 3] = [0x10, 0x10, 0x10];
+
+const ANSI_16: [[u8; 3]; 16] = [
+  [0x00, 0x00, 0x00],
+  [0xcd, 0x31, 0x31],
+  [0x0d, 0xbc, 0x79],
+  [0xe5, 0xe5, 0x10],
+  [0x24, 0x72, 0xc8],
+  [0xbc, 0x3f, 0xbc],
+  [0x11, 0xa8, 0xcd],
+  [0xe5, 0xe5, 0xe5],
+  [0x66, 0x66, 0x66],
+  [0xf1, 0x4c, 0x4c],
+  [0x23, 0xd1, 0x8b],
+  [0xf5, 0xf5, 0x43],
+  [0x3b, 0x8e, 0xea],
+  [0xd6, 0x70, 0xd6],
+  [0x29, 0xb8, 0xdb],
+  [0xff, 0xff, 0xff],
+];
+
+#[derive(Clone, Dbg, PartialEq)]
+pub struct Span {
+  pub text: Str,
+  pub fg: [u8; 3],
+  pub bg: [u8; 3],
+  pub bold: bool,
+}
+
+pub fn rgb(color: Color, default: [u8; 3]) -> [u8; 3] {
+  match color {
+    Color::Default => default,
+    Color::Idx(i @ 0..=15) => ANSI_16[i as usize],
+    Color::Idx(i @ 16..=231) => {
+      let level = |v: u8| if 0 == v { 0 } else { 55 + v * 40 };
+      let i = i - 16;
+      [level(i / 36), level(i / 6 % 6), level(i % 6)]
+    }
+    Color::Idx(i) => [8 + (i - 232) * 10; 3],
+    Color::Rgb(r, g, b) => [r, g, b],
+  }
+}
+
+fn style(cell: &Cell, cursor: bool) -> ([u8; 3], [u8; 3], bool) {
+  let fg = rgb(cell.fgcolor(), TERM_FG);
+  let bg = rgb(cell.bgcolor(), TERM_BG);
+  if cell.inverse() != cursor {
+    (bg, fg, cell.bold())
+  } else {
+    (fg, bg, cell.bold())
+  }
+}
+
+pub struct Term {
+  parser: Parser,
+}
+
+impl Default for Term {
+  fn default() -> Self {
+    Self { parser: Parser::new(TERM_ROWS, TERM_COLS, 0) }
+  }
+}
+
+impl Term {
+  pub fn feed(&mut self, data: &[u8]) {
+    self.parser.process(data);
+  }
+
+  pub fn clear(&mut self) {
+    *self = Self::default();
+  }
+
+  pub fn app_cursor(&self) -> bool {
+    self.parser.screen().application_cursor()
+  }
+
+  pub fn rows(&self) -> Vec<Vec<Span>> {
+    let screen = self.parser.screen();
+    let (rows, cols) = screen.size();
+    let cursor = if screen.hide_cursor() { None } else { Some(screen.cursor_position()) };
+    (0..rows)
+      .map(|row| {
+        let mut spans: Vec<Span> = Vec::new();
+        for col in 0..cols {
+          let Some(cell) = screen.cell(row, col) else { continue };
+          if cell.is_wide_continuation() {
+            continue;
+          }
+          let (fg, bg, bold) = style(cell, Some((row, col)) == cursor);
+          let text = if cell.has_contents() { cell.contents() } else { " " };
+          match spans.last_mut() {
+            Some(last) if (last.fg, last.bg, last.bold) == (fg, bg, bold) => last.text += text,
+            _ => spans.push(Span { text: text.into(), fg, bg, bold }),
+          }
+        }
+        spans
+      })
+      .collect()
+  }
+}
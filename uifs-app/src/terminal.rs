@@ -0,0 +1,98 @@
+use crate::{AppWindow, TermRow, TermSpan, Terminal, WEAK_APP};
+use core::cell::{Cell, RefCell};
+use slint::platform::Key;
+use slint::{Color, ComponentHandle, ModelRc};
+use uifs_app::term::{Term, TERM_BG};
+
+thread_local! {
+  static TERM: RefCell<Term> = RefCell::new(Term::default());
+  static DIRTY: Cell<bool> = const { Cell::new(true) };
+}
+
+const CSI_KEYS: [(Key, &str); 12] = [
+  (Key::Backtab, "Z"),
+  (Key::Home, "H"),
+  (Key::End, "F"),
+  (Key::Insert, "2~"),
+  (Key::Delete, "3~"),
+  (Key::PageUp, "5~"),
+  (Key::PageDown, "6~"),
+  (Key::F1, "11~"),
+  (Key::F2, "12~"),
+  (Key::F3, "13~"),
+  (Key::F4, "14~"),
+  (Key::F5, "15~"),
+];
+
+const CURSOR_KEYS: [(Key, u8); 4] =
+  [(Key::UpArrow, b'A'), (Key::DownArrow, b'B'), (Key::RightArrow, b'C'), (Key::LeftArrow, b'D')];
+
+fn color([r, g, b]: [u8; 3]) -> Color {
+  Color::from_rgb_u8(r, g, b)
+}
+
+pub fn feed(data: &[u8]) {
+  TERM.with_borrow_mut(|term| term.feed(data));
+  DIRTY.set(true);
+}
+
+pub fn render() {
+  if !DIRTY.replace(false) {
+    return;
+  }
+  let rows: Vec<_> = TERM.with_borrow(|term| {
+    term
+      .rows()
+      .into_iter()
+      .map(|spans| {
+        let spans: Vec<_> = spans
+          .into_iter()
+          .map(|span| TermSpan {
+            text: span.text.into(),
+            fg: color(span.fg),
+            bg: color(span.bg),
+            bold: span.bold,
+          })
+          .collect();
+        TermRow { spans: ModelRc::from(spans.as_slice()) }
+      })
+      .collect()
+  });
+  let app = WEAK_APP.with(|w| w.get().unwrap().unwrap());
+  app.global::<Terminal>().set_rows(ModelRc::from(rows.as_slice()));
+}
+
+pub fn key_bytes(text: &str, ctrl: bool, alt: bool) -> Vec<u8> {
+  let mut chars = text.chars();
+  let (Some(c), None) = (chars.next(), chars.next()) else { return text.as_bytes().to_vec() };
+  let mut out = if alt { vec![0x1b] } else { Vec::new() };
+  if let Some(&(_, dir)) = CURSOR_KEYS.iter().find(|&&(k, _)| c == char::from(k)) {
+    let app_cursor = TERM.with_borrow(Term::app_cursor);
+    out.extend([0x1b, if app_cursor { b'O' } else { b'[' }, dir]);
+  } else if let Some(&(_, seq)) = CSI_KEYS.iter().find(|&&(k, _)| c == char::from(k)) {
+    out.extend(b"\x1b[");
+    out.extend(seq.as_bytes());
+  } else if c == char::from(Key::Return) {
+    out.push(b'\r');
+  } else if c == char::from(Key::Backspace) {
+    out.push(0x7f);
+  } else if ctrl && c.is_ascii() && ('@'..='_').contains(&c.to_ascii_uppercase()) {
+    out.push(c.to_ascii_uppercase() as u8 & 0x1f);
+  } else if ('\u{10}'..='\u{18}').contains(&c) || ('\u{f700}'..='\u{f8ff}').contains(&c) {
+    return Vec::new();
+  } else {
+    out.extend(text.as_bytes());
+  }
+  out
+}
+
+pub fn init(app: &AppWindow) {
+  let term = app.global::<Terminal>();
+  term.set_background(color(TERM_BG));
+  term.on_clear(|| {
+    TERM.with_borrow_mut(Term::clear);
+    DIRTY.set(true);
+    render();
+  });
+  render();
+}
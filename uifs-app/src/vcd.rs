@@ -0,0 +1,92 @@
+use crate::capture::Dir;
+use crate::obsr::{SampleTp, Traces};
+use crate::{f, Str};
+use std::io::{self, Write};
+
+fn var_id(idx: usize) -> Str {
+  let (mut idx, mut id) = (idx, Str::new());
+  loop {
+    id.push((b'!' + (idx % 94) as u8) as char);
+    idx /= 94;
+    if 0 == idx {
+      return id;
+    }
+    idx -= 1;
+  }
+}
+
+fn us(t: f64) -> u64 {
+  (t.max(0.0) * 1e6).round() as u64
+}
+
+pub fn write_vcd(w: &mut impl Write, traces: &Traces, frms: &[(f64, Dir, u8)]) -> io::Result<()> {
+  writeln!(w, "$version uifs {} $end", env!("CARGO_PKG_VERSION"))?;
+  writeln!(w, "$timescale 1us $end")?;
+  writeln!(w, "$scope module uifs $end")?;
+
+  let mut changes: Vec<(u64, Str)> = Vec::new();
+  let mut idx = 0;
+  for (ch, trace) in traces.chans() {
+    let id = var_id(idx);
+    idx += 1;
+    let mut last = None;
+    let points = trace.points.iter().map(|&(t, v)| {
+      let t = last.map_or(us(t), |last: u64| us(t).max(last + 1));
+      last = Some(t);
+      (t, v)
+    });
+    if SampleTp::F32 == trace.tp {
+      writeln!(w, "$var real 64 {id} ch{ch} $end")?;
+      let points = points.filter(|(_, v)| v.is_finite());
+      changes.extend(points.map(|(t, v)| (t, f!("r{v} {id}"))));
+    } else {
+      let bits = trace.tp.size() * 8;
+      writeln!(w, "$var wire {bits} {id} ch{ch} [{}:0] $end", bits - 1)?;
+      changes.extend(points.map(|(t, v)| (t, f!("b{:b} {id}", v as i64 as u32))));
+    }
+  }
+  if !frms.is_empty() {
+    for (dir, name) in [(Dir::Tx, "tx"), (Dir::Rx, "rx")] {
+      let (evt_id, op_id) = (var_id(idx), var_id(idx + 1));
+      idx += 2;
+      writeln!(w, "$var event 1 {evt_id} {name}_frm $end")?;
+      writeln!(w, "$var wire 8 {op_id} {name}_op [7:0] $end")?;
+      for &(t, _, op) in frms.iter().filter(|(_, d, _)| dir == *d) {
+        changes.push((us(t), f!("1{evt_id}")));
+        changes.push((us(t), f!("b{op:b} {op_id}")));
+      }
+    }
+  }
+  writeln!(w, "$upscope $end")?;
+  writeln!(w, "$enddefinitions $end")?;
+
+  changes.sort_by_key(|(t, _)| *t);
+  let mut cur = None;
+  for (t, change) in changes {
+    if Some(t) != cur {
+      writeln!(w, "#{t}")?;
+      cur = Some(t);
+    }
+    writeln!(w, "{change}")?;
+  }
+  w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::obsr::Obsr;
+
+  #[test]
+  fn distinct_sample_times() {
+    let mut traces = Traces::new();
+    traces.push(0.5, &Obsr { ch: 1, tp: SampleTp::U8, samples: vec![1.0, 2.0, 3.0] });
+    traces.push(0.500_002, &Obsr { ch: 1, tp: SampleTp::U8, samples: vec![4.0] });
+    let mut buf = Vec::new();
+    write_vcd(&mut buf, &traces, &[]).unwrap();
+    let text = String::from_utf8(buf).unwrap();
+    let body = text.split("$enddefinitions $end\n").nth(1).unwrap();
+    let expected = "#500000\nb1 !\n#500001\nb10 !\n#500002\nb11 !\n#500003\nb100 !\n";
+    assert_eq!(expected, body);
+  }
+}
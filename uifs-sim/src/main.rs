@@ -0,0 +1,168 @@
+#![cfg_attr(not(unix), allow(dead_code, unused_imports))]
+use bytes::{BufMut, Bytes, BytesMut};
+use core::time::Duration as Durn;
+use uifs_app::protocol::{BlockMode, FrmDec, OpFlag};
+use uifs_app::soft;
+use uifs_app::*;
+
+const USAGE: &str = "\
+用法：uifs-sim [--latency <毫秒>] [--link <路径>]
+
+  --latency <毫秒>  每个响应帧发送前的延迟，默认 0
+  --link <路径>     额外创建指向伪终端从端的符号链接";
+
+#[derive(Dbg, Default)]
+struct Conf {
+  latency: Durn,
+  link: Opt<std::path::PathBuf>,
+}
+
+impl Conf {
+  fn from_args() -> Rst<Self> {
+    let mut conf = Self::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+      match arg.as_str() {
+        "--latency" => {
+          let Some(ms) = args.next() else {
+            we!("--latency 缺少参数\n{USAGE}");
+          };
+          match ms.parse() {
+            Ok(ms) => conf.latency = Durn::from_millis(ms),
+            Err(e) => {
+              we!("{}\n{USAGE}", mk_err_str(e, "无法解析延迟"));
+            }
+          }
+        }
+        "--link" => {
+          let Some(path) = args.next() else {
+            we!("--link 缺少参数\n{USAGE}");
+          };
+          conf.link = Some(path.into());
+        }
+        "-h" | "--help" => {
+          println!("{USAGE}");
+          std::process::exit(0);
+        }
+        _ => {
+          we!("未知参数：{arg}\n{USAGE}");
+        }
+      }
+    }
+    Ok(conf)
+  }
+}
+
+#[derive(Default)]
+struct Dev {
+  key: [u8; KEY_LEN],
+}
+
+impl Dev {
+  fn rsp(op: OpFlag, mode: u8, payload: &[u8]) -> Bytes {
+    let frm_len = FRM_HEAD_LEN + payload.len() + FRM_TAIL_LEN;
+    let mut buf = BytesMut::with_capacity(frm_len);
+    buf.put_u8(FRM_START_FLAG);
+    buf.put_u16(frm_len as u16);
+    buf.put_u8(op as u8);
+    buf.put_u8(mode);
+    buf.put_slice(payload);
+    buf.put_u16(FRM_PAR_FLAG);
+    buf.freeze()
+  }
+
+  fn answer(&mut self, frm: &[u8]) -> Opt<Bytes> {
+    let payload = &frm[FRM_HEAD_LEN..frm.len() - FRM_TAIL_LEN];
+    let Ok(op) = OpFlag::try_from(frm[3]) else {
+      eprintln!("未知操作码：{}", frm[3]);
+      return None;
+    };
+    match op {
+      OpFlag::Key => {
+        let Ok(k) = payload.try_into() else {
+          eprintln!("密钥长度有误：{}", payload.len());
+          return None;
+        };
+        self.key = k;
+        Some(Self::rsp(op, FRM_PRESERVE_FLAG, &[0x01]))
+      }
+      OpFlag::Sm3 => Some(Self::rsp(op, FRM_PRESERVE_FLAG, &soft::sm3(payload))),
+      OpFlag::Sm4Enc | OpFlag::Sm4Dec => {
+        let Ok(mode) = BlockMode::try_from(frm[4]) else {
+          eprintln!("未知分组模式：{}", frm[4]);
+          return None;
+        };
+        let (iv, data) = match mode {
+          BlockMode::ECB => ([0u8; IV_LEN].as_slice(), payload),
+          BlockMode::CBC if payload.len() >= IV_LEN => payload.split_at(IV_LEN),
+          BlockMode::CBC => {
+            eprintln!("CBC 帧缺少初始向量");
+            return None;
+          }
+        };
+        let iv = iv.try_into().unwrap();
+        Some(Self::rsp(op, mode as u8, &soft::sm4(&self.key, op == OpFlag::Sm4Enc, mode, iv, data)))
+      }
+    }
+  }
+}
+
+#[cfg(unix)]
+fn main() -> Rst<()> {
+  use serialport::SerialPort;
+  use std::io::{ErrorKind, Read, Write};
+
+  let conf = Conf::from_args()?;
+  let (mut master, slave) = match serialport::TTYPort::pair() {
+    Ok(pair) => pair,
+    Err(e) => {
+      we!("{}", mk_err_str(e, "无法创建伪终端"));
+    }
+  };
+  let Some(slave_name) = slave.name() else {
+    we!("无法获取伪终端从端路径");
+  };
+  if let Some(link) = &conf.link {
+    let _ = std::fs::remove_file(link);
+    if let Err(e) = std::os::unix::fs::symlink(&slave_name, link) {
+      we!("{}", mk_err_str(e, "无法创建符号链接"));
+    }
+  }
+  println!("模拟器已就绪：{slave_name}");
+  println!("启动上位机时设置 UIFS_EXTRA_SPS={slave_name} 以在串口列表中显示");
+
+  let mut dev = Dev::default();
+  let mut dec = FrmDec::default();
+  let mut tmp = vec![0u8; FRM_MAX_LEN];
+  loop {
+    match master.read(&mut tmp) {
+      Ok(n) => dec.push(&tmp[..n]),
+      Err(e) if ErrorKind::TimedOut == e.kind() => continue,
+      Err(e) => {
+        we!("{}", mk_err_str(e, "读取伪终端失败"));
+      }
+    }
+    for frm in &mut dec {
+      let frm = match frm {
+        Ok(frm) => frm,
+        Err(e) => {
+          eprintln!("{e}");
+          continue;
+        }
+      };
+      println!("收到：{}", const_hex::encode(&frm));
+      let Some(rsp) = dev.answer(&frm) else { continue };
+      std::thread::sleep(conf.latency);
+      if let Err(e) = master.write_all(&rsp) {
+        we!("{}", mk_err_str(e, "写入伪终端失败"));
+      }
+      println!("响应：{}", const_hex::encode(&rsp));
+    }
+  }
+}
+
+#[cfg(not(unix))]
+fn main() {
+  eprintln!("uifs-sim 依赖伪终端，仅支持类 Unix 系统");
+  std::process::exit(1);
+}
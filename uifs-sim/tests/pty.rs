@@ -0,0 +1,89 @@
+#![cfg(unix)]
+use bytes::Bytes;
+use core::time::Duration as Durn;
+use serialport::SerialPort;
+use std::io::ErrorKind;
+use std::process::{Child, Command, Stdio};
+use std::time::Instant;
+use uifs_app::protocol::{self, BlockMode, FrmDec, Rsp};
+use uifs_app::soft;
+
+struct Sim {
+  child: Child,
+  link: std::path::PathBuf,
+}
+
+impl Sim {
+  fn start() -> Self {
+    let link = std::env::temp_dir().join(format!("uifs-sim-test-{}", std::process::id()));
+    let child = Command::new(env!("CARGO_BIN_EXE_uifs-sim"))
+      .arg("--link")
+      .arg(&link)
+      .stdout(Stdio::null())
+      .spawn()
+      .unwrap();
+    let deadline = Instant::now() + Durn::from_secs(5);
+    while !link.exists() {
+      assert!(Instant::now() < deadline, "模拟器未就绪");
+      std::thread::sleep(Durn::from_millis(20));
+    }
+    Self { child, link }
+  }
+
+  fn open(&self) -> Box<dyn SerialPort> {
+    serialport::new(self.link.to_string_lossy(), 115200)
+      .timeout(Durn::from_millis(50))
+      .open()
+      .unwrap()
+  }
+}
+
+impl Drop for Sim {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+    let _ = std::fs::remove_file(&self.link);
+  }
+}
+
+fn request(sp: &mut dyn SerialPort, dec: &mut FrmDec, req: &Bytes) -> Rsp {
+  sp.write_all(req).unwrap();
+  let deadline = Instant::now() + Durn::from_secs(5);
+  let mut buf = [0u8; 1024];
+  loop {
+    if let Some(frm) = dec.next() {
+      return Rsp::parse(&frm.unwrap()).unwrap();
+    }
+    assert!(Instant::now() < deadline, "等待响应超时");
+    match sp.read(&mut buf) {
+      Ok(n) => dec.push(&buf[..n]),
+      Err(e) if ErrorKind::TimedOut == e.kind() => {}
+      Err(e) => panic!("{e}"),
+    }
+  }
+}
+
+#[test]
+fn key_sm3_sm4_round_trip() {
+  let sim = Sim::start();
+  let mut sp = sim.open();
+  let mut dec = FrmDec::default();
+  let key: [u8; 16] = *b"0123456789abcdef";
+  let iv: [u8; 16] = *b"fedcba9876543210";
+  let msg = b"uifs simulator round trip test!!";
+
+  assert_eq!(Rsp::Key, request(sp.as_mut(), &mut dec, &protocol::key(&key)));
+  assert_eq!(Rsp::Sm3(soft::sm3(msg)), request(sp.as_mut(), &mut dec, &protocol::sm3(msg)));
+
+  let ct = soft::sm4(&key, true, BlockMode::ECB, &[0; 16], msg);
+  let rsp = request(sp.as_mut(), &mut dec, &protocol::sm4_enc_ecb(msg));
+  assert_eq!(Rsp::Sm4Enc(BlockMode::ECB, Bytes::from(ct.clone())), rsp);
+  let rsp = request(sp.as_mut(), &mut dec, &protocol::sm4_dec_ecb(&ct));
+  assert_eq!(Rsp::Sm4Dec(BlockMode::ECB, Bytes::from_static(msg)), rsp);
+
+  let ct = soft::sm4(&key, true, BlockMode::CBC, &iv, msg);
+  let rsp = request(sp.as_mut(), &mut dec, &protocol::sm4_enc_cbc(msg, &iv));
+  assert_eq!(Rsp::Sm4Enc(BlockMode::CBC, Bytes::from(ct.clone())), rsp);
+  let rsp = request(sp.as_mut(), &mut dec, &protocol::sm4_dec_cbc(&ct, &iv));
+  assert_eq!(Rsp::Sm4Dec(BlockMode::CBC, Bytes::from_static(msg)), rsp);
+}